@@ -0,0 +1,109 @@
+/*
+ * Preprocessing benchmark: the separate resize, YUYV conversion and tensor copy
+ * the server used to run against preprocess_into, which does all three in one
+ * pass. Allocations are counted by a wrapper around the system allocator, which
+ * is why this module only builds with the bench feature.
+ */
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::convert::BT601_FULL;
+use crate::pool::BufferPool;
+use crate::types::{Image, PixelFormat, ResampleMode};
+use crate::utils::{preprocess_into, resize_with_padding_ultra_fast, yuv422_to_rgb24};
+
+const WIDTH: i32 = 640;
+const HEIGHT: i32 = 480;
+// the lightning model input
+const INPUT_SIZE: i32 = 192;
+const ITERATIONS: u32 = 200;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+// Milliseconds, allocations and allocated bytes per call of `run`
+fn measure(mut run: impl FnMut()) -> (f64, f64, f64) {
+    // warm up rayon's threads, so their start does not count
+    run();
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+    let per_call = |total: f64| total / ITERATIONS as f64;
+    (per_call(elapsed), per_call(allocations as f64), per_call(bytes as f64))
+}
+
+/** Times the path from a 640x480 YUYV frame to a 192x192 RGB input tensor, for
+** every resample mode: first resize_with_padding_ultra_fast, yuv422_to_rgb24 and
+** a copy into the tensor, each allocating its own buffers as the server did
+** before preprocess_into, then preprocess_into writing the tensor directly.
+**/
+pub fn run_preprocess_benchmark() {
+    // deterministic noise so every code path gets exercised
+    let mut state: u32 = 0x1234_5678;
+    let yuyv: Vec<u8> = (0..WIDTH * HEIGHT * 2)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 24) as u8
+        })
+        .collect();
+    let image = Image::new(yuyv, WIDTH, HEIGHT, PixelFormat::YUYV);
+    // stands in for the interpreter input tensor, which the server does not allocate
+    let mut tensor = vec![0u8; (INPUT_SIZE * INPUT_SIZE * 3) as usize];
+
+    println!(
+        "YUYV {}x{} -> RGB {}x{} input tensor, {} iterations, per frame",
+        WIDTH, HEIGHT, INPUT_SIZE, INPUT_SIZE, ITERATIONS
+    );
+    for mode in [ResampleMode::Nearest, ResampleMode::Bilinear, ResampleMode::Area] {
+        let separate = measure(|| {
+            // a pool that keeps nothing allocates every buffer, like the old path
+            let resized = resize_with_padding_ultra_fast(&image, (INPUT_SIZE, INPUT_SIZE), mode, &BufferPool::new(0));
+            let mut rgb = vec![0u8; resized.data.len() * 3 / 2];
+            yuv422_to_rgb24(&BT601_FULL, &resized.data, &mut rgb);
+            tensor.copy_from_slice(&rgb);
+        });
+        let fused = measure(|| preprocess_into(&image, &mut tensor, (INPUT_SIZE, INPUT_SIZE), mode));
+
+        for (name, (ms, allocations, bytes)) in [("separate", separate), ("fused", fused)] {
+            println!(
+                "  {:<8} {:<8} {:7.3} ms ({:4.1}x)   {:6.1} allocations   {:9.0} bytes allocated",
+                format!("{:?}", mode), name, ms, separate.0 / ms, allocations, bytes
+            );
+        }
+    }
+}
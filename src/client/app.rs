@@ -1,67 +1,162 @@
-use std::slice;
-use opencv::{
-    prelude::*,
-    highgui::*,
-};
+use opencv::highgui::*;
 
-use crate::client::camera::Camera;
 use crate::client::server_client::ServerClient;
-use crate::types::COLOR_SPACE::{YUV};
 use crate::types::{InferenceResults};
-use crate::utils::{draw_keypoints};
-use crate::types::Image;
+use crate::types::{monotonic_micros, DisplayConverter, Image, Mirror, PixelFormat, Rotation};
+use crate::keypoints;
+use crate::draw;
+use crate::pool::BufferPool;
+use crate::record::Recorder;
+use crate::source::{FrameSource, SourceError, SourceStatus};
+use log::{debug, error, info, warn};
 
 
-pub struct App {
+pub struct App<S: FrameSource> {
     server_client: ServerClient,
-    cam: Camera
+    source: S,
+    rotation: Rotation,
+    mirror: Mirror,
+    swap_sides: bool,
+    display_converter: DisplayConverter,
+    // frame copies and display conversions, both returned after imshow
+    pool: BufferPool,
+    recorder: Option<Recorder>,
+    // last status seen, and the size of the last frame for the disconnected screen
+    status: SourceStatus,
+    frame_size: (i32, i32)
 }
 
-impl App {
-    /** Makes a new App struct. Must take in both a camera and a server client
-    ** that are already initialized
+impl<S: FrameSource> App<S> {
+    /** Makes a new App struct. Must take in both a frame source and a server
+    ** client that are already initialized. `rotation` is how this camera is mounted,
+    ** the server turns frames upright and maps keypoints back to the raw frame.
+    ** `mirror` flips the displayed frame and its keypoints together, and
+    ** `swap_sides` relabels left and right joints to match.
     **/
-    pub fn new(server_client: ServerClient, cam: Camera) -> Self {
+    pub fn new(
+        server_client: ServerClient,
+        source: S,
+        rotation: Rotation,
+        mirror: Mirror,
+        swap_sides: bool,
+        display_converter: DisplayConverter
+    ) -> Self {
 
-        App { server_client: server_client, cam: cam }
+        App { server_client, source, rotation, mirror, swap_sides, display_converter, pool: BufferPool::new(2), recorder: None,
+            status: SourceStatus::Live, frame_size: (640, 480) }
     }
     
-    // Processes a frame from the camera, the entire pipeline
-    pub fn process_frame(&mut self) {
-        self.capture_image();
-        let buffer_ptr = self.cam.get_buffer_ptr(); // Cast to a known type, e.g., u8
-        let buffer_len = self.cam.get_buffer_length(); // Calculate the length
-
-        // Create a slice from the raw pointer
-        let buffer_slice = unsafe {
-            assert!(!buffer_ptr.is_null()); // Ensure the pointer is not null
-            slice::from_raw_parts(buffer_ptr, buffer_len)
+    // Processes a frame from the source, the entire pipeline. Returns without a
+    // frame when the source has none yet
+    pub fn process_frame(&mut self) -> Result<(), SourceError> {
+        let frame = self.source.next_frame(&self.pool)?;
+        self.update_status();
+        let Some(mut img) = frame else {
+            return Ok(());
         };
+        self.frame_size = (img.width, img.height);
+        // a full disk stops the recording, not the session
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.write(&img) {
+                error!("{}, recording stopped", e);
+                self.recorder = None;
+            }
+        }
 
-        self.server_client.send_data(&buffer_slice[..], 640, 480);
-        let results = self.server_client.receive_results();
+        // MJPEG frames go to the server still compressed
+        self.server_client.send_data(&img.data, &img.frame_info(self.rotation));
+        let mut results = self.server_client.receive_results();
+        info!(
+            "frame {}: results {} ms after capture, {} frames dropped so far",
+            results.sequence,
+            monotonic_micros().saturating_sub(results.timestamp) / 1000,
+            self.source.dropped_frames()
+        );
 
-        let data_clone = buffer_slice.to_vec();
-        let mut img = Image::new(data_clone, 640, 480, YUV);
+        // selfie mode, the keypoints follow the frame
+        if let Err(e) = img.mirror(self.mirror) {
+            // webcams do send the odd truncated frame, skip showing it
+            warn!("Dropping undecodable {:?} frame {}: {}", img.pixel_format, img.sequence, e);
+            self.pool.recycle(img);
+            return Ok(());
+        }
+        keypoints::mirror(&mut results.vector, self.mirror);
+        if self.swap_sides {
+            keypoints::swap_left_right(&mut results.vector);
+        }
 
-        // let mut resized = resize_with_padding_ultra_fast(&img, (192, 192), YUV);
-        let mut rgb_yuv_rgb = img.to_mat();
+        self.display_results(img, &results);
+        debug!("buffer pool {}", self.pool.stats());
+        Ok(())
+    }
 
 
-        self.display_results(&mut rgb_yuv_rgb, &results);
+    // Writes every frame the source delivers to `recorder`, as it came
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
 
+    // Writes the index of the recording, if any, and returns how many frames it holds
+    pub fn finish_recording(&mut self) -> Result<Option<usize>, String> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(None);
+        };
+        let frames = recorder.frame_count();
+        recorder.finish()?;
+        Ok(Some(frames))
     }
 
+    // Reports the source going away and coming back, and keeps the window
+    // saying so while it is away
+    fn update_status(&mut self) {
+        let status = self.source.status();
+        if status != self.status {
+            match status {
+                SourceStatus::Disconnected => warn!("camera disconnected: {}", self.source.name()),
+                SourceStatus::Live => info!("camera reconnected: {}", self.source.name()),
+            }
+            self.status = status;
+        }
+        if status == SourceStatus::Disconnected {
+            self.display_disconnected();
+        }
+    }
 
-    // Captures an image from the camera
-    pub fn capture_image(&mut self) {
-        self.cam.capture_image().expect("Capture image error");
+    fn display_disconnected(&self) {
+        let (width, height) = self.frame_size;
+        let mut img = self.pool.take_image(width, height, PixelFormat::BGR);
+        img.data.fill(0);
+        let text = "CAMERA DISCONNECTED";
+        let scale = (width / (text.len() as i32 * (draw::GLYPH_WIDTH + 1) * 2)).max(1);
+        let text_width = text.len() as i32 * (draw::GLYPH_WIDTH + 1) * scale;
+        let position = ((width - text_width) / 2, (height - draw::GLYPH_HEIGHT * scale) / 2);
+        draw::draw_text(&mut img, position, text, scale, draw::Color::WHITE);
+        let frame = img.to_mat_with(self.display_converter);
+        imshow("MoveNet", &frame).expect("imshow [ERROR]");
+        self.pool.recycle(img);
     }
 
     // Displays the inference results on the captured image
-    pub fn display_results(&self, frame: &mut Mat, results: &InferenceResults) {
-        // Logic to draw keypoints on the image and display it
-        draw_keypoints(frame, &results.vector[..], 0.25);
-        imshow("MoveNet", frame).expect("imshow [ERROR]");
+    pub fn display_results(&self, img: Image, results: &InferenceResults) {
+        // draw in the camera format when we can, so the display converter still sees the raw frame
+        let mut img = match img.pixel_format {
+            PixelFormat::RGB | PixelFormat::BGR | PixelFormat::GREY | PixelFormat::YUYV | PixelFormat::UYVY => img,
+            _ => {
+                let bgr = img.to_bgr_pooled(&self.pool);
+                let sequence = img.sequence;
+                self.pool.recycle(img);
+                match bgr {
+                    Ok(bgr) => bgr,
+                    Err(e) => {
+                        warn!("Dropping undecodable frame {}: {}", sequence, e);
+                        return;
+                    }
+                }
+            }
+        };
+        draw::draw_pose(&mut img, &results.vector[..], 0.25);
+        let frame = img.to_mat_with(self.display_converter);
+        imshow("MoveNet", &frame).expect("imshow [ERROR]");
+        self.pool.recycle(img);
     }
 }
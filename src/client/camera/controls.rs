@@ -0,0 +1,353 @@
+use std::fmt;
+use std::os::fd::AsRawFd;
+use nix::ioctl_readwrite;
+use log::info;
+
+use crate::client::camera::bindings::*;
+use crate::client::camera::Camera;
+
+const VIDIOC_MAGIC: u8 = b'V';
+
+// #define VIDIOC_G_CTRL		_IOWR('V', 27, struct v4l2_control)
+ioctl_readwrite!(vidioc_g_ctrl, VIDIOC_MAGIC, 27, v4l2_control);
+// #define VIDIOC_S_CTRL		_IOWR('V', 28, struct v4l2_control)
+ioctl_readwrite!(vidioc_s_ctrl, VIDIOC_MAGIC, 28, v4l2_control);
+// #define VIDIOC_QUERYCTRL	_IOWR('V', 36, struct v4l2_queryctrl)
+ioctl_readwrite!(vidioc_queryctrl, VIDIOC_MAGIC, 36, v4l2_queryctrl);
+// #define VIDIOC_QUERYMENU	_IOWR('V', 37, struct v4l2_querymenu)
+ioctl_readwrite!(vidioc_querymenu, VIDIOC_MAGIC, 37, v4l2_querymenu);
+// #define VIDIOC_G_EXT_CTRLS	_IOWR('V', 71, struct v4l2_ext_controls)
+ioctl_readwrite!(vidioc_g_ext_ctrls, VIDIOC_MAGIC, 71, v4l2_ext_controls);
+// #define VIDIOC_S_EXT_CTRLS	_IOWR('V', 72, struct v4l2_ext_controls)
+ioctl_readwrite!(vidioc_s_ext_ctrls, VIDIOC_MAGIC, 72, v4l2_ext_controls);
+// #define VIDIOC_QUERY_EXT_CTRL	_IOWR('V', 103, struct v4l2_query_ext_ctrl)
+ioctl_readwrite!(vidioc_query_ext_ctrl, VIDIOC_MAGIC, 103, v4l2_query_ext_ctrl);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlKind {
+    Integer,
+    Boolean,
+    Menu,
+    IntegerMenu,
+    Button,
+    Integer64,
+    // strings, bitmasks and compound controls, listed but not settable here
+    Other(u32),
+}
+
+impl ControlKind {
+    // the constants keep their bindgen names
+    #[allow(non_upper_case_globals)]
+    fn from_v4l2(type_: u32) -> ControlKind {
+        match type_ {
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER => ControlKind::Integer,
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_BOOLEAN => ControlKind::Boolean,
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_MENU => ControlKind::Menu,
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER_MENU => ControlKind::IntegerMenu,
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_BUTTON => ControlKind::Button,
+            v4l2_ctrl_type_V4L2_CTRL_TYPE_INTEGER64 => ControlKind::Integer64,
+            other => ControlKind::Other(other),
+        }
+    }
+}
+
+// One entry of a menu control; integer menus have a value instead of a name
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+    pub index: i64,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlInfo {
+    pub id: u32,
+    // the driver's name, e.g. "Exposure Time, Absolute"
+    pub name: String,
+    // the name used to look the control up, e.g. "exposure_time_absolute"
+    pub key: String,
+    pub kind: ControlKind,
+    pub minimum: i64,
+    pub maximum: i64,
+    pub step: i64,
+    pub default: i64,
+    pub flags: u32,
+    pub menu: Vec<MenuItem>,
+    // None for write-only controls and buttons
+    pub value: Option<i64>,
+}
+
+impl ControlInfo {
+    pub fn is_read_only(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_READ_ONLY != 0
+    }
+
+    // Inactive controls are overridden by another one, e.g. exposure under auto exposure
+    pub fn is_inactive(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_INACTIVE != 0
+    }
+
+    /** Parses a value for this control: a number within its range, on/off for
+    ** booleans, and for menus either the index or the entry name in key form,
+    ** e.g. "manual_mode" for "Manual Mode".
+    **/
+    pub fn parse_value(&self, text: &str) -> Result<i64, String> {
+        let text = text.trim();
+        let value = match self.kind {
+            ControlKind::Boolean => match text.to_lowercase().as_str() {
+                "1" | "true" | "on" | "yes" => 1,
+                "0" | "false" | "off" | "no" => 0,
+                _ => return Err(format!("{}: expected on or off, got '{}'", self.key, text)),
+            },
+            ControlKind::Menu | ControlKind::IntegerMenu => match text.parse::<i64>() {
+                Ok(index) if self.menu.iter().any(|item| item.index == index) => index,
+                _ => match self.menu.iter().find(|item| control_key(&item.label) == control_key(text)) {
+                    Some(item) => item.index,
+                    None => {
+                        let choices: Vec<String> = self.menu.iter().map(|item| format!("{} ({})", item.index, control_key(&item.label))).collect();
+                        return Err(format!("{}: '{}' is not one of {}", self.key, text, choices.join(", ")));
+                    }
+                },
+            },
+            ControlKind::Integer | ControlKind::Integer64 | ControlKind::Button => text.parse::<i64>()
+                .map_err(|_| format!("{}: '{}' is not a number", self.key, text))?,
+            ControlKind::Other(type_) => return Err(format!("{}: controls of type {} are not supported", self.key, type_)),
+        };
+        if self.kind != ControlKind::Button && (value < self.minimum || value > self.maximum) {
+            return Err(format!("{}: {} is outside {}..={}", self.key, value, self.minimum, self.maximum));
+        }
+        Ok(value)
+    }
+}
+
+impl fmt::Display for ControlInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<32} {:#010x} {:?} min={} max={} step={} default={}",
+            self.key, self.id, self.kind, self.minimum, self.maximum, self.step, self.default)?;
+        if let Some(value) = self.value {
+            write!(f, " value={}", value)?;
+        }
+        if self.is_read_only() {
+            write!(f, " [read-only]")?;
+        }
+        if self.is_inactive() {
+            write!(f, " [inactive]")?;
+        }
+        for item in &self.menu {
+            write!(f, "\n{:>36} {}: {}", "", item.index, item.label)?;
+        }
+        Ok(())
+    }
+}
+
+// Lowercase with runs of anything else turned into single underscores, as v4l2-ctl names controls
+pub fn control_key(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+/** Reads control presets, one `name = value` per line. Blank lines and
+** everything after a '#' are ignored. Names and values are checked against the
+** camera only when the presets are applied.
+**/
+pub fn parse_presets(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut presets = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() && !value.trim().is_empty() => {
+                presets.push((name.trim().to_string(), value.trim().to_string()));
+            }
+            _ => return Err(format!("line {}: expected name = value, got '{}'", number + 1, line)),
+        }
+    }
+    Ok(presets)
+}
+
+fn name_of(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+impl Camera {
+    /** Lists every control the driver exposes, with its range, default, menu
+    ** entries and current value. Disabled controls and the class headings
+    ** V4L2 puts between groups are left out.
+    **/
+    pub fn list_controls(&self) -> Result<Vec<ControlInfo>, String> {
+        let media_fd = self.file.as_raw_fd();
+        let mut controls = Vec::new();
+        let mut query: v4l2_queryctrl = unsafe { std::mem::zeroed() };
+        query.id = V4L2_CTRL_FLAG_NEXT_CTRL;
+        while unsafe { vidioc_queryctrl(media_fd, &mut query) }.is_ok() {
+            let kind = ControlKind::from_v4l2(query.type_);
+            let skip = query.flags & V4L2_CTRL_FLAG_DISABLED != 0
+                || query.type_ == v4l2_ctrl_type_V4L2_CTRL_TYPE_CTRL_CLASS;
+            if !skip {
+                let name = name_of(&query.name);
+                let mut control = ControlInfo {
+                    id: query.id,
+                    key: control_key(&name),
+                    name,
+                    kind,
+                    minimum: query.minimum as i64,
+                    maximum: query.maximum as i64,
+                    step: query.step as i64,
+                    default: query.default_value as i64,
+                    flags: query.flags,
+                    menu: Vec::new(),
+                    value: None,
+                };
+                if kind == ControlKind::Integer64 {
+                    self.query_range64(&mut control);
+                }
+                if matches!(kind, ControlKind::Menu | ControlKind::IntegerMenu) {
+                    control.menu = self.query_menu(&control);
+                }
+                let readable = query.flags & V4L2_CTRL_FLAG_WRITE_ONLY == 0
+                    && matches!(kind, ControlKind::Integer | ControlKind::Boolean | ControlKind::Menu | ControlKind::IntegerMenu | ControlKind::Integer64);
+                if readable {
+                    control.value = self.read_control(control.id, kind).ok();
+                }
+                controls.push(control);
+            }
+            query.id |= V4L2_CTRL_FLAG_NEXT_CTRL;
+        }
+        Ok(controls)
+    }
+
+    // QUERYCTRL clamps the range of 64-bit controls to 32 bits, the extended query
+    // has it in full. Kernels without it keep the clamped range
+    fn query_range64(&self, control: &mut ControlInfo) {
+        let mut query: v4l2_query_ext_ctrl = unsafe { std::mem::zeroed() };
+        query.id = control.id;
+        if unsafe { vidioc_query_ext_ctrl(self.file.as_raw_fd(), &mut query) }.is_ok() {
+            control.minimum = query.minimum;
+            control.maximum = query.maximum;
+            control.step = query.step as i64;
+            control.default = query.default_value;
+        }
+    }
+
+    // Menus may have holes, indices the driver rejects are skipped
+    fn query_menu(&self, control: &ControlInfo) -> Vec<MenuItem> {
+        let media_fd = self.file.as_raw_fd();
+        let mut items = Vec::new();
+        for index in control.minimum.max(0)..=control.maximum {
+            let mut menu: v4l2_querymenu = unsafe { std::mem::zeroed() };
+            menu.id = control.id;
+            menu.index = index as u32;
+            if unsafe { vidioc_querymenu(media_fd, &mut menu) }.is_err() {
+                continue;
+            }
+            let label = match control.kind {
+                ControlKind::IntegerMenu => unsafe { menu.__bindgen_anon_1.value }.to_string(),
+                _ => name_of(&unsafe { menu.__bindgen_anon_1.name }),
+            };
+            items.push(MenuItem { index, label });
+        }
+        items
+    }
+
+    // Looks a control up by its key or driver name
+    pub fn find_control(&self, name: &str) -> Result<ControlInfo, String> {
+        let key = control_key(name);
+        self.list_controls()?
+            .into_iter()
+            .find(|control| control.key == key)
+            .ok_or_else(|| format!("camera has no control named '{}'", name))
+    }
+
+    // 64-bit controls only fit the extended ioctls, everything else uses G_CTRL
+    fn read_control(&self, id: u32, kind: ControlKind) -> Result<i64, String> {
+        let media_fd = self.file.as_raw_fd();
+        if kind == ControlKind::Integer64 {
+            let mut control: v4l2_ext_control = unsafe { std::mem::zeroed() };
+            control.id = id;
+            let mut controls: v4l2_ext_controls = unsafe { std::mem::zeroed() };
+            controls.__bindgen_anon_1.which = V4L2_CTRL_WHICH_CUR_VAL;
+            controls.count = 1;
+            controls.controls = &mut control;
+            return match unsafe { vidioc_g_ext_ctrls(media_fd, &mut controls) } {
+                Ok(_) => Ok(unsafe { control.__bindgen_anon_1.value64 }),
+                Err(e) => Err(format!("get control {:#x} [FAILED]: {:?}", id, e)),
+            };
+        }
+
+        let mut control = v4l2_control { id, value: 0 };
+        match unsafe { vidioc_g_ctrl(media_fd, &mut control) } {
+            Ok(_) => Ok(control.value as i64),
+            Err(e) => Err(format!("get control {:#x} [FAILED]: {:?}", id, e)),
+        }
+    }
+
+    fn write_control(&self, id: u32, kind: ControlKind, value: i64) -> Result<(), String> {
+        let media_fd = self.file.as_raw_fd();
+        if kind == ControlKind::Integer64 {
+            let mut control: v4l2_ext_control = unsafe { std::mem::zeroed() };
+            control.id = id;
+            control.__bindgen_anon_1.value64 = value;
+            let mut controls: v4l2_ext_controls = unsafe { std::mem::zeroed() };
+            controls.__bindgen_anon_1.which = V4L2_CTRL_WHICH_CUR_VAL;
+            controls.count = 1;
+            controls.controls = &mut control;
+            return match unsafe { vidioc_s_ext_ctrls(media_fd, &mut controls) } {
+                Ok(_) => Ok(()),
+                Err(e) => Err(format!("set control {:#x} [FAILED]: {:?}", id, e)),
+            };
+        }
+
+        let value = i32::try_from(value).map_err(|_| format!("set control {:#x} [FAILED]: {} does not fit 32 bits", id, value))?;
+        let mut control = v4l2_control { id, value };
+        match unsafe { vidioc_s_ctrl(media_fd, &mut control) } {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("set control {:#x} [FAILED]: {:?}", id, e)),
+        }
+    }
+
+    pub fn get_control_by_name(&self, name: &str) -> Result<i64, String> {
+        let control = self.find_control(name)?;
+        self.read_control(control.id, control.kind)
+    }
+
+    // `value` is parsed as ControlInfo::parse_value describes
+    pub fn set_control_by_name(&self, name: &str, value: &str) -> Result<(), String> {
+        let control = self.find_control(name)?;
+        if control.is_read_only() {
+            return Err(format!("{} is read-only", control.key));
+        }
+        let value = control.parse_value(value)?;
+        self.write_control(control.id, control.kind, value)?;
+        info!("control {} = {}", control.key, value);
+        Ok(())
+    }
+
+    /** Applies presets in order, so a file can switch auto exposure or focus
+    ** off before it sets the manual value. Every name and value is checked
+    ** before anything is written, and the first failure stops the rest.
+    **/
+    pub fn apply_controls(&self, presets: &[(String, String)]) -> Result<(), String> {
+        let controls = self.list_controls()?;
+        let mut resolved = Vec::with_capacity(presets.len());
+        for (name, value) in presets {
+            let key = control_key(name);
+            let control = controls.iter()
+                .find(|control| control.key == key)
+                .ok_or_else(|| format!("camera has no control named '{}'", name))?;
+            if control.is_read_only() {
+                return Err(format!("{} is read-only", control.key));
+            }
+            resolved.push((control, control.parse_value(value)?));
+        }
+        for (control, value) in resolved {
+            self.write_control(control.id, control.kind, value)?;
+            info!("control {} = {}", control.key, value);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::prelude::AsRawFd;
+use std::path::{Path, PathBuf};
+use nix::ioctl_read;
+use log::{info, warn};
+
+use crate::client::camera::bindings::*;
+
+const VIDIOC_MAGIC: u8 = b'V';
+const BY_ID_DIR: &str = "/dev/v4l/by-id";
+
+// A capture device found by list_cameras
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraInfo {
+    pub path: PathBuf,
+    pub driver: String,
+    pub card: String,
+    pub bus_info: String,
+    // udev links in /dev/v4l/by-id, stable across reboots unlike the index
+    pub by_id: Vec<PathBuf>,
+}
+
+impl fmt::Display for CameraInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} ({}, {})", self.path.display(), self.card, self.driver, self.bus_info)?;
+        for link in &self.by_id {
+            write!(f, "\n    {}", link.display())?;
+        }
+        Ok(())
+    }
+}
+
+// The NUL padded strings of v4l2_capability
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn query_camera(path: &Path) -> Result<Option<CameraInfo>, String> {
+    // non-blocking, so a camera another process is streaming from still answers
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map_err(|e| format!("open {} [FAILED]: {}", path.display(), e))?;
+
+    // #define VIDIOC_QUERYCAP		 _IOR('V',  0, struct v4l2_capability)
+    ioctl_read!(vidioc_querycap, VIDIOC_MAGIC, 0, v4l2_capability);
+    let mut caps: v4l2_capability = unsafe { std::mem::zeroed() };
+    unsafe { vidioc_querycap(file.as_raw_fd(), &mut caps) }
+        .map_err(|e| format!("querycap {} [FAILED]: {}", path.display(), e))?;
+
+    // capabilities covers the whole device, device_caps just this node; UVC
+    // cameras have a second node for metadata that only the latter tells apart
+    let node_caps = match caps.capabilities & V4L2_CAP_DEVICE_CAPS {
+        0 => caps.capabilities,
+        _ => caps.device_caps,
+    };
+    let wanted = V4L2_CAP_VIDEO_CAPTURE | V4L2_CAP_STREAMING;
+    if node_caps & wanted != wanted {
+        info!("{} cannot stream video capture, skipping", path.display());
+        return Ok(None);
+    }
+
+    Ok(Some(CameraInfo {
+        path: path.to_path_buf(),
+        driver: c_string(&caps.driver),
+        card: c_string(&caps.card),
+        bus_info: c_string(&caps.bus_info),
+        by_id: Vec::new(),
+    }))
+}
+
+// by-id links grouped by the device node they point at
+fn by_id_links() -> Vec<(PathBuf, PathBuf)> {
+    let Ok(entries) = fs::read_dir(BY_ID_DIR) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let target = fs::canonicalize(entry.path()).ok()?;
+            Some((target, entry.path()))
+        })
+        .collect()
+}
+
+/** Scans /dev/video* for nodes that can stream video capture, sorted by index.
+** Nodes that cannot be opened or queried are skipped with a warning, so one
+** broken device does not hide the others.
+**/
+pub fn list_cameras() -> Result<Vec<CameraInfo>, String> {
+    let entries = fs::read_dir("/dev").map_err(|e| format!("read /dev [FAILED]: {}", e))?;
+    let mut nodes: Vec<(u32, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let index = name.to_str()?.strip_prefix("video")?.parse().ok()?;
+            Some((index, entry.path()))
+        })
+        .collect();
+    nodes.sort();
+
+    let links = by_id_links();
+    let mut cameras = Vec::new();
+    for (_, path) in nodes {
+        match query_camera(&path) {
+            Ok(Some(mut camera)) => {
+                camera.by_id = links.iter()
+                    .filter(|(target, _)| *target == path)
+                    .map(|(_, link)| link.clone())
+                    .collect();
+                camera.by_id.sort();
+                cameras.push(camera);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+    Ok(cameras)
+}
+
+/** Finds the camera `selector` names. It may be an index (0 for /dev/video0),
+** a device path or /dev/v4l/by-id link, a bus_info such as usb-0000:00:14.0-1,
+** or a card name, compared without case. A card name shared by several cameras
+** is an error, since picking one of them would depend on the index again.
+**/
+pub fn find_camera(selector: &str) -> Result<CameraInfo, String> {
+    let cameras = list_cameras()?;
+    let path = match selector.parse::<u32>() {
+        Ok(index) => Some(PathBuf::from(format!("/dev/video{}", index))),
+        Err(_) if selector.starts_with('/') => Some(
+            fs::canonicalize(selector).map_err(|e| format!("camera {} [FAILED]: {}", selector, e))?
+        ),
+        Err(_) => None,
+    };
+
+    let matches: Vec<&CameraInfo> = match &path {
+        Some(path) => cameras.iter().filter(|camera| camera.path == *path).collect(),
+        None => {
+            let by_bus: Vec<&CameraInfo> = cameras.iter().filter(|camera| camera.bus_info == selector).collect();
+            if by_bus.is_empty() {
+                cameras.iter().filter(|camera| camera.card.eq_ignore_ascii_case(selector)).collect()
+            } else {
+                by_bus
+            }
+        }
+    };
+
+    match matches[..] {
+        [camera] => Ok(camera.clone()),
+        [] => Err(format!("no capture camera matches '{}', see --list-cameras", selector)),
+        _ => {
+            let paths: Vec<String> = matches.iter().map(|camera| camera.path.display().to_string()).collect();
+            Err(format!("'{}' matches {}, select by bus_info or by-id path instead", selector, paths.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::os::fd::RawFd;
+use std::str;
+use nix::ioctl_readwrite;
+use log::info;
+
+use crate::client::camera::bindings::*;
+use crate::convert::Colorimetry;
+use crate::types::PixelFormat;
+
+const VIDIOC_MAGIC: u8 = b'V';
+
+// Used when the request leaves the format open: raw YUV first, it is the
+// cheapest to ship and convert, then MJPEG before the less common layouts
+const FORMAT_PREFERENCE: [PixelFormat; 9] = [
+    PixelFormat::YUYV,
+    PixelFormat::UYVY,
+    PixelFormat::NV12,
+    PixelFormat::YU12,
+    PixelFormat::MJPEG,
+    PixelFormat::RGB,
+    PixelFormat::BGR,
+    PixelFormat::RGB565,
+    PixelFormat::GREY,
+];
+
+// Time between frames in seconds, as the fraction V4L2 uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInterval {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameInterval {
+    pub fn from_fps(fps: u32) -> FrameInterval {
+        FrameInterval { numerator: 1, denominator: fps }
+    }
+
+    pub fn fps(&self) -> f32 {
+        match self.numerator {
+            0 => 0.0,
+            numerator => self.denominator as f32 / numerator as f32,
+        }
+    }
+}
+
+// One format, size and frame rate combination a camera offers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureMode {
+    pub pixel_format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    pub interval: FrameInterval,
+}
+
+// What we would like from the camera, `pixel_format` None takes any format we can read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureRequest {
+    pub pixel_format: Option<PixelFormat>,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+// The format the driver accepted, which downstream code sizes its frames by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureFormat {
+    pub pixel_format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    pub bytes_per_line: usize,
+    pub interval: FrameInterval,
+    pub colorimetry: Colorimetry,
+}
+
+/** Picks the mode that best fits the request. Sizes that cover the requested
+** one beat smaller sizes, which would have to be upscaled; among those the
+** closest pixel count wins. Then frame rates that keep up with the request beat
+** slower ones, closest first, and format preference breaks the remaining ties.
+**/
+pub fn choose_mode(modes: &[CaptureMode], request: &CaptureRequest) -> Option<CaptureMode> {
+    let requested_area = request.width as i64 * request.height as i64;
+    modes.iter()
+        .filter(|mode| request.pixel_format.is_none_or(|format| format == mode.pixel_format))
+        .min_by_key(|mode| {
+            let too_small = mode.width < request.width || mode.height < request.height;
+            let area_distance = (mode.width as i64 * mode.height as i64 - requested_area).abs();
+            // 1% slack so 29.97 fps counts as keeping up with 30
+            let too_slow = mode.interval.fps() * 1.01 < request.fps as f32;
+            // in thousandths of a frame per second, so the key stays an integer
+            let fps_distance = ((mode.interval.fps() - request.fps as f32).abs() * 1000.0) as u32;
+            let rank = FORMAT_PREFERENCE.iter().position(|format| *format == mode.pixel_format);
+            (too_small, area_distance, too_slow, fps_distance, rank)
+        })
+        .copied()
+}
+
+/** Lists every mode the camera offers in a format we can read. Stepwise and
+** continuous ranges are not expanded: they contribute their smallest and
+** largest entries plus the one closest to the request. Drivers that cannot
+** enumerate sizes or intervals are assumed to take the requested ones.
+**/
+pub fn enumerate_modes(media_fd: RawFd, request: &CaptureRequest) -> Result<Vec<CaptureMode>, String> {
+    // #define VIDIOC_ENUM_FMT         _IOWR('V',  2, struct v4l2_fmtdesc)
+    ioctl_readwrite!(vidioc_enum_fmt, VIDIOC_MAGIC, 2, v4l2_fmtdesc);
+
+    let mut modes = Vec::new();
+    let mut fmtdesc: v4l2_fmtdesc = unsafe { std::mem::zeroed() };
+    fmtdesc.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
+    while unsafe { vidioc_enum_fmt(media_fd, &mut fmtdesc) }.is_ok() {
+        let description = str::from_utf8(&fmtdesc.description).unwrap_or("?").trim_end_matches('\0');
+        match PixelFormat::from_fourcc(fmtdesc.pixelformat) {
+            Some(pixel_format) => {
+                info!("format {}: {:?} ({})", fmtdesc.index, pixel_format, description);
+                for (width, height) in frame_sizes(media_fd, fmtdesc.pixelformat, request) {
+                    for interval in frame_intervals(media_fd, fmtdesc.pixelformat, (width, height), request) {
+                        info!("  {}x{} @ {:.2} fps", width, height, interval.fps());
+                        modes.push(CaptureMode { pixel_format, width, height, interval });
+                    }
+                }
+            }
+            None => info!("format {}: {} is not supported, skipping", fmtdesc.index, description),
+        }
+        fmtdesc.index += 1;
+    }
+
+    if modes.is_empty() {
+        return Err("enumerate formats [FAILED]: camera offers no format we can read".to_string());
+    }
+    Ok(modes)
+}
+
+// The value in min..=max reachable in `step`s from min that is closest to `target`
+fn snap(target: u32, min: u32, max: u32, step: u32) -> u32 {
+    let target = target.clamp(min, max);
+    match step {
+        0 | 1 => target,
+        step => (min + (target - min + step / 2) / step * step).min(max),
+    }
+}
+
+fn frame_sizes(media_fd: RawFd, fourcc: u32, request: &CaptureRequest) -> Vec<(u32, u32)> {
+    // #define VIDIOC_ENUM_FRAMESIZES	_IOWR('V', 74, struct v4l2_frmsizeenum)
+    ioctl_readwrite!(vidioc_enum_framesizes, VIDIOC_MAGIC, 74, v4l2_frmsizeenum);
+
+    let mut sizes = Vec::new();
+    let mut frmsize: v4l2_frmsizeenum = unsafe { std::mem::zeroed() };
+    frmsize.pixel_format = fourcc;
+    while unsafe { vidioc_enum_framesizes(media_fd, &mut frmsize) }.is_ok() {
+        if frmsize.type_ == v4l2_frmsizetypes_V4L2_FRMSIZE_TYPE_DISCRETE {
+            let discrete = unsafe { frmsize.__bindgen_anon_1.discrete };
+            sizes.push((discrete.width, discrete.height));
+        } else {
+            // continuous and stepwise sizes come as a single entry
+            let range = unsafe { frmsize.__bindgen_anon_1.stepwise };
+            sizes.push((range.min_width, range.min_height));
+            sizes.push((
+                snap(request.width, range.min_width, range.max_width, range.step_width),
+                snap(request.height, range.min_height, range.max_height, range.step_height),
+            ));
+            sizes.push((range.max_width, range.max_height));
+            break;
+        }
+        frmsize.index += 1;
+    }
+
+    if sizes.is_empty() {
+        sizes.push((request.width, request.height));
+    }
+    sizes.dedup();
+    sizes
+}
+
+fn frame_intervals(media_fd: RawFd, fourcc: u32, (width, height): (u32, u32), request: &CaptureRequest) -> Vec<FrameInterval> {
+    // #define VIDIOC_ENUM_FRAMEINTERVALS	_IOWR('V', 75, struct v4l2_frmivalenum)
+    ioctl_readwrite!(vidioc_enum_frameintervals, VIDIOC_MAGIC, 75, v4l2_frmivalenum);
+
+    let to_interval = |fract: v4l2_fract| FrameInterval { numerator: fract.numerator, denominator: fract.denominator };
+    let mut intervals = Vec::new();
+    let mut frmival: v4l2_frmivalenum = unsafe { std::mem::zeroed() };
+    frmival.pixel_format = fourcc;
+    frmival.width = width;
+    frmival.height = height;
+    while unsafe { vidioc_enum_frameintervals(media_fd, &mut frmival) }.is_ok() {
+        if frmival.type_ == v4l2_frmivaltypes_V4L2_FRMIVAL_TYPE_DISCRETE {
+            intervals.push(to_interval(unsafe { frmival.__bindgen_anon_1.discrete }));
+        } else {
+            // the shortest interval is the highest frame rate
+            let range = unsafe { frmival.__bindgen_anon_1.stepwise };
+            let (fastest, slowest) = (to_interval(range.min), to_interval(range.max));
+            intervals.push(fastest);
+            if (slowest.fps()..=fastest.fps()).contains(&(request.fps as f32)) {
+                intervals.push(FrameInterval::from_fps(request.fps));
+            }
+            intervals.push(slowest);
+            break;
+        }
+        frmival.index += 1;
+    }
+
+    if intervals.is_empty() {
+        intervals.push(FrameInterval::from_fps(request.fps));
+    }
+    intervals.dedup();
+    intervals
+}
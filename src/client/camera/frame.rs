@@ -0,0 +1,112 @@
+use std::slice;
+use log::{error, info, warn};
+
+use crate::client::camera::{Camera, CaptureError, CaptureFormat};
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, SourceError};
+use crate::types::Image;
+
+/** A captured frame, still in the mmapped buffer the driver filled. It borrows
+** the camera, so no other frame can be captured and the camera cannot be
+** stopped or dropped while it is alive; dropping it hands the buffer back to
+** the driver. Copy the data out first if it has to live longer than that.
+**/
+pub struct Frame<'a> {
+    camera: &'a Camera,
+    index: u32,
+    length: usize,
+    timestamp: u64,
+    sequence: u32,
+}
+
+impl<'a> Frame<'a> {
+    // `length` is what the driver filled, clamped to the buffer
+    pub(crate) fn new(camera: &'a Camera, index: u32, length: usize, timestamp: u64, sequence: u32) -> Frame<'a> {
+        let length = match length {
+            0 => camera.buffers[index as usize].length,
+            length => length.min(camera.buffers[index as usize].length),
+        };
+        Frame { camera, index, length, timestamp, sequence }
+    }
+
+    // The bytes the driver filled, varies from frame to frame for MJPEG
+    pub fn data(&self) -> &[u8] {
+        let buffer = &self.camera.buffers[self.index as usize];
+        // the driver only writes to buffers that are queued, and this one is not until drop
+        unsafe { slice::from_raw_parts(buffer.buffer as *const u8, self.length) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn format(&self) -> CaptureFormat {
+        self.camera.format
+    }
+
+    // CLOCK_MONOTONIC microseconds at which the frame was captured
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    // Driver sequence number, see Camera::get_dropped_frames
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    // Copies the frame into a pooled Image, MJPEG frames stay compressed
+    pub fn to_image(&self, pool: &BufferPool) -> Image {
+        let format = self.format();
+        let mut data = pool.take(self.len());
+        data.copy_from_slice(self.data());
+        Image::with_stride(data, format.width as i32, format.height as i32, format.bytes_per_line, format.pixel_format)
+            .with_colorimetry(format.colorimetry)
+            .with_capture_info(self.timestamp, self.sequence)
+    }
+}
+
+impl<'a> Drop for Frame<'a> {
+    fn drop(&mut self) {
+        match self.camera.queue(self.index) {
+            Ok(_) => info!("IOCTL: qbuf [OK]: buffer {}", self.index),
+            // the driver has one buffer less to fill from now on
+            Err(e) => error!("qbuf {} [FAILED]: {}", self.index, e),
+        }
+    }
+}
+
+impl Camera {
+    /** Waits for the next frame and copies it into a pooled Image; dropping the
+    ** Frame right away requeues the buffer while the consumer works. A stall or
+    ** an empty non-blocking queue is Ok(None), other errors are left to the caller.
+    **/
+    pub fn next_image(&mut self, pool: &BufferPool) -> Result<Option<Image>, CaptureError> {
+        match self.capture_frame() {
+            Ok(frame) => Ok(Some(frame.to_image(pool))),
+            Err(CaptureError::Timeout(timeout)) => {
+                warn!("camera stalled, no frame within {:?}", timeout);
+                Ok(None)
+            }
+            Err(CaptureError::WouldBlock) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl FrameSource for Camera {
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        self.next_image(pool).map_err(|e| SourceError::Failed(e.to_string()))
+    }
+
+    fn name(&self) -> String {
+        format!("{} {:?} {}x{}", self.path.display(), self.format.pixel_format, self.format.width, self.format.height)
+    }
+
+    fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+}
@@ -1,14 +1,33 @@
 mod bindings;
+mod controls;
+mod discovery;
+mod format;
+mod frame;
+mod reconnect;
 
 use crate::client::camera::bindings::*;
 
 use std::{fs::File, os::unix::prelude::AsRawFd, str};
+use std::fmt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use nix::errno::Errno;
 use nix::{sys::ioctl, ioctl_read, ioctl_readwrite, ioctl_write_ptr};
 use std::mem::size_of;
 use std::os::fd::RawFd;
 use nix::libc::{ioctl, KERN_ACPI_VIDEO_FLAGS};
 
-use log::{info, warn, error};
+use log::{debug, info, warn, error};
+
+use crate::types::{monotonic_micros, PixelFormat};
+use crate::convert::{Colorimetry, YuvMatrix, YuvRange};
+
+pub use controls::{control_key, parse_presets, ControlInfo, ControlKind, MenuItem};
+pub use discovery::{find_camera, list_cameras, CameraInfo};
+pub use format::{choose_mode, enumerate_modes, CaptureFormat, CaptureMode, CaptureRequest, FrameInterval};
+pub use frame::Frame;
+pub use reconnect::{CameraSetup, ReconnectingCamera};
 
 use libc::mmap;
 // #define VIDIOC_QUERYCAP		 _IOR('V',  0, struct v4l2_capability)
@@ -16,67 +35,121 @@ use libc::mmap;
 const VIDIOC_QUERYCAP_MAGIC: u8 = 'V' as u8;
 const VIDIOC_QUERYCAP_TYPE_MODE: u8 = 0;
 
+/** Why capture_frame came back without a frame. A timeout or a spurious wake-up
+** leaves the camera usable and the caller can simply try again; a device error
+** usually does not, ENODEV for instance means the camera was unplugged.
+**/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureError {
+    Timeout(Duration),
+    // EAGAIN, nothing to dequeue after all
+    WouldBlock,
+    Device(Errno),
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureError::Timeout(timeout) => write!(f, "no frame within {:?}", timeout),
+            CaptureError::WouldBlock => write!(f, "no frame ready"),
+            CaptureError::Device(errno) => write!(f, "camera device error: {}", errno),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl CaptureError {
+    // The camera is gone, unplugged most likely; only reopening it helps
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self, CaptureError::Device(Errno::ENODEV))
+    }
+}
+
 struct MmappedBuffer {
     pub buffer: *mut libc::c_void,
     pub length: usize
 }
 
+/** Capture uses a queue of mmapped buffers. All of them start out queued;
+** capture_frame dequeues whichever one the driver filled first and hands it to
+** the consumer as a Frame, and it goes back to the driver when the Frame is
+** dropped. Meanwhile the driver keeps filling the others, so frames are only
+** dropped once the consumer holds on longer than the queue lasts.
+**/
 pub struct Camera {
     file: File,
-    pub buffer: MmappedBuffer,
+    path: PathBuf,
+    buffers: Vec<MmappedBuffer>,
     fps: i32,
     streaming: bool,
+    format: CaptureFormat,
+    // sequence of the previous frame, gaps in the numbering are dropped frames
+    last_sequence: Option<u32>,
+    dropped_frames: u64,
+    // how long capture_frame waits for a frame
+    timeout: Duration,
 }
 
 impl Camera {
-    pub fn new(index: u32) -> Self {
-
-        // C: open camera device
+    /** Opens the device at `path` in the mode that best fits `request`, see
+    ** choose_mode. The camera may still adjust it, get_format has what it
+    ** accepted. `buffer_count` is a request too, the driver may grant more or
+    ** fewer buffers. find_camera turns a name or index into a path.
+    **/
+    pub fn new(path: &Path, request: &CaptureRequest, buffer_count: u32) -> Result<Self, String> {
+
+        // C: open camera device, non-blocking so a stalled camera cannot hang us in DQBUF
         let mut file = File::options()
             .write(true)
             .read(true)
-            .open(format!("/dev/video{}", index))
-            .unwrap();
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .map_err(|e| format!("open {} [FAILED]: {}", path.display(), e))?;
         let media_fd = file.as_raw_fd();
         info!("camera fd = {}", media_fd);
 
-        if media_fd < 0 {
-            panic!("failed to open camera device");
-        }
-
-        let init_device_result = Camera::init_device(&media_fd);
+        let init_device_result = Camera::init_device(&media_fd, request);
+        let format: CaptureFormat;
         match init_device_result {
-            Ok(fmt) => {
-                info!("init device [OK]");
+            Ok(accepted) => {
+                info!("init device [OK]: {:?}", accepted);
+                format = accepted;
             }
             Err(e) => {
-                panic!("init device [FAILED]: {:?}", e);
+                return Err(format!("init device [FAILED]: {}", e));
             }
         }
 
-        let init_buffer_result = Camera::allocate_and_mmap(&media_fd);
-        let buffer: MmappedBuffer;
+        let init_buffer_result = Camera::allocate_and_mmap(&media_fd, buffer_count);
+        let buffers: Vec<MmappedBuffer>;
         match init_buffer_result {
-            Ok(inner_buffer) => {
-                info!("allocate and mmap [OK]");
-                buffer = inner_buffer;
+            Ok(inner_buffers) => {
+                info!("allocate and mmap [OK]: {} buffers", inner_buffers.len());
+                buffers = inner_buffers;
             }
             Err(e) => {
-                panic!("allocate and mmap [FAILED]: {:?}", e);
+                return Err(format!("allocate and mmap [FAILED]: {}", e));
             }
         }
 
 
-        Camera {
+        Ok(Camera {
             file: file,
-            buffer: buffer,
-            fps: 0,
+            path: path.to_path_buf(),
+            buffers: buffers,
+            fps: format.interval.fps().round() as i32,
             streaming: false,
-        }
+            format,
+            last_sequence: None,
+            dropped_frames: 0,
+            timeout: Duration::from_secs(1),
+        })
 
     }
 
-    pub fn init_device(media_fd1: &RawFd) -> Result<bool, String> {
+    // Returns the format the driver settled on, with its stride, frame rate and colorimetry
+    pub fn init_device(media_fd1: &RawFd, request: &CaptureRequest) -> Result<CaptureFormat, String> {
         let media_fd = *media_fd1;
         // 2: query capabilities
         let mut info: v4l2_capability = unsafe { std::mem::zeroed() };
@@ -155,36 +228,23 @@ impl Camera {
             }
         }
 
-        // C: check supported image format
-        let mut fmtdesc: v4l2_fmtdesc = unsafe { std::mem::zeroed() };
-        fmtdesc.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-        fmtdesc.index = 0;
+        // C: check supported formats, sizes and frame rates and pick one
         info!("===Getting supported format===");
-        // #define VIDIOC_ENUM_FMT         _IOWR('V',  2, struct v4l2_fmtdesc)
-        ioctl_readwrite!(vidioc_enum_fmt, VIDIOC_QUERYCAP_MAGIC, 2, v4l2_fmtdesc);
-
-        loop {
-            match unsafe { vidioc_enum_fmt(media_fd, &mut fmtdesc) } {
-                Ok(_) => {
-                    info!("IOCTL: get supported format [OK]");
-                    info!("index: {:?}", fmtdesc.index);
-                    info!("pixelformat: {:?}", fmtdesc.pixelformat);
-                    info!("description: {:?}", str::from_utf8(&fmtdesc.description));
-                }
-                Err(e) => {
-                    break;
-                }
+        let modes = enumerate_modes(media_fd, request)?;
+        let mode = match choose_mode(&modes, request) {
+            Some(mode) => mode,
+            None => {
+                return Err(format!("camera does not support {:?}", request.pixel_format));
             }
-            fmtdesc.index += 1;
-        }
+        };
+        info!("picked {:?} {}x{} @ {:.2} fps", mode.pixel_format, mode.width, mode.height, mode.interval.fps());
 
-        // switch to YUYV format
+        // switch to the chosen format
         // #define VIDIOC_S_FMT		_IOWR('V', 5, struct v4l2_format)
-        // format.fmt.pix.pixelformat = 0x56595559; // 'V', 'Y', 'U', 'Y'
         unsafe {
-            fmt.fmt.pix.pixelformat = 0x56595559; // 'V', 'Y', 'U', 'Y'
-            fmt.fmt.pix.width = 192;
-            fmt.fmt.pix.height = 192;
+            fmt.fmt.pix.pixelformat = mode.pixel_format.fourcc();
+            fmt.fmt.pix.width = mode.width;
+            fmt.fmt.pix.height = mode.height;
         }
         ioctl_readwrite!(vidio_s_fmt, VIDIOC_QUERYCAP_MAGIC, 5, v4l2_format);
         match unsafe { vidio_s_fmt(media_fd, &mut fmt) } {
@@ -215,13 +275,44 @@ impl Camera {
             }
         }
 
-        return Ok(true);
+        // drivers silently fall back to another format when they lack the requested one
+        let pix = unsafe { fmt.fmt.pix };
+        if pix.pixelformat != mode.pixel_format.fourcc() {
+            return Err(format!(
+                "camera does not support {:?}, driver picked fourcc {:?}",
+                mode.pixel_format, str::from_utf8(&pix.pixelformat.to_le_bytes())
+            ));
+        }
+        if (pix.width, pix.height) != (mode.width, mode.height) {
+            warn!("asked for {}x{}, driver picked {}x{}", mode.width, mode.height, pix.width, pix.height);
+        }
+
+        let interval = match Camera::set_frame_interval(media_fd, mode.interval) {
+            Ok(interval) => interval,
+            Err(e) => {
+                warn!("{}, assuming {:.2} fps", e, mode.interval.fps());
+                mode.interval
+            }
+        };
+
+        return Ok(CaptureFormat {
+            pixel_format: mode.pixel_format,
+            width: pix.width,
+            height: pix.height,
+            // rows may be padded for alignment, 0 means the driver left it to us
+            bytes_per_line: match pix.bytesperline {
+                0 => mode.pixel_format.packed_stride(pix.width as usize),
+                bytes_per_line => bytes_per_line as usize,
+            },
+            interval,
+            colorimetry: colorimetry_of(&pix),
+        });
     }
-    pub fn allocate_and_mmap(media_fd1: &RawFd) -> Result<MmappedBuffer, String> {
+    pub fn allocate_and_mmap(media_fd1: &RawFd, buffer_count: u32) -> Result<Vec<MmappedBuffer>, String> {
         let media_fd = *media_fd1;
         // 1: request and allocate buffers
         let mut reqbufs: v4l2_requestbuffers = unsafe { std::mem::zeroed() };
-        reqbufs.count = 4;
+        reqbufs.count = buffer_count;
         reqbufs.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
         reqbufs.memory = v4l2_memory_V4L2_MEMORY_MMAP;
 
@@ -237,13 +328,35 @@ impl Camera {
                 return Err("request buffer [FAILED]".to_string());
             }
         }
+        if reqbufs.count == 0 {
+            return Err("request buffer [FAILED]: driver granted no buffers".to_string());
+        }
+        if reqbufs.count != buffer_count {
+            warn!("requested {} buffers, driver granted {}", buffer_count, reqbufs.count);
+        }
+
+        // 2: map every granted buffer, undoing the earlier maps if one fails
+        let mut buffers: Vec<MmappedBuffer> = Vec::with_capacity(reqbufs.count as usize);
+        for index in 0..reqbufs.count {
+            match Camera::query_and_mmap(media_fd, index) {
+                Ok(buffer) => buffers.push(buffer),
+                Err(e) => {
+                    for buffer in buffers {
+                        unsafe { libc::munmap(buffer.buffer, buffer.length); }
+                    }
+                    return Err(format!("buffer {}: {}", index, e));
+                }
+            }
+        }
+        Ok(buffers)
+    }
 
-        // 2: get details of the allocated buffer and map it
+    fn query_and_mmap(media_fd: RawFd, index: u32) -> Result<MmappedBuffer, String> {
         // #define VIDIOC_QUERYBUF		_IOWR('V', 9, struct v4l2_buffer)
         let mut buf: v4l2_buffer = unsafe { std::mem::zeroed() };
         buf.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
         buf.memory = v4l2_memory_V4L2_MEMORY_MMAP;
-        buf.index = 0;
+        buf.index = index;
 
         ioctl_readwrite!(vidioc_querybuf, VIDIOC_QUERYCAP_MAGIC, 9, v4l2_buffer);
         match unsafe { vidioc_querybuf(media_fd, &mut buf) } {
@@ -285,35 +398,34 @@ impl Camera {
         })
     }
 
-    pub fn set_frame_rate(&mut self, new_fps: i32) -> Result<bool, String> {
-        let media_fd = self.file.as_raw_fd();
+    // Returns the interval the driver actually uses, which may differ from the one asked for
+    fn set_frame_interval(media_fd: RawFd, interval: FrameInterval) -> Result<FrameInterval, String> {
         // #define VIDIOC_S_PARM		_IOWR('V', 22, struct v4l2_streamparm)
         let mut parm: v4l2_streamparm = unsafe { std::mem::zeroed() };
 
         unsafe {
             parm.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-            parm.parm.capture.timeperframe.denominator = new_fps as u32;
-            parm.parm.capture.timeperframe.numerator = 1;
+            parm.parm.capture.timeperframe.denominator = interval.denominator;
+            parm.parm.capture.timeperframe.numerator = interval.numerator;
             parm.parm.capture.capability = V4L2_CAP_TIMEPERFRAME;
-            parm.parm.output.timeperframe.denominator = new_fps as u32;
-            parm.parm.output.timeperframe.numerator = 1;
-            parm.parm.output.capability = V4L2_CAP_TIMEPERFRAME;
         }
 
         ioctl_readwrite!(vidioc_s_parm, VIDIOC_QUERYCAP_MAGIC, 22, v4l2_streamparm);
         match unsafe { vidioc_s_parm(media_fd, &mut parm) } {
             Ok(_) => {
-                info!("IOCTL: set frame rate [OK]");
-                self.fps = new_fps;
-                return Ok(true);
+                let accepted = unsafe { parm.parm.capture.timeperframe };
+                let accepted = FrameInterval { numerator: accepted.numerator, denominator: accepted.denominator };
+                info!("IOCTL: set frame rate [OK]: {:.2} fps", accepted.fps());
+                // drivers that ignore the request may leave it zeroed
+                Ok(if accepted.numerator == 0 { interval } else { accepted })
             }
             Err(e) => {
-                return Err(format!("set frame rate [FAILED]: {:?}", e));
+                Err(format!("set frame rate [FAILED]: {:?}", e))
             }
         }
     }
 
-    pub fn start_capture(&self) -> Result<bool, String> {
+    pub fn start_capture(&mut self) -> Result<bool, String> {
         if self.fps == 0 {
             return Err("frame rate not set".to_string());
         }
@@ -321,6 +433,12 @@ impl Camera {
             return Ok(true);
         }
         let media_fd = self.file.as_raw_fd();
+
+        // the driver needs empty buffers queued before it can start filling them
+        for index in 0..self.buffers.len() as u32 {
+            self.enqueue_buffer(index)?;
+        }
+
         // #define VIDIOC_STREAMON		_IOW('V', 18, int)
         ioctl_write_ptr!(vidioc_streamon, VIDIOC_QUERYCAP_MAGIC, 18, i32);
         let mut stream_type = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE as i32;
@@ -332,11 +450,14 @@ impl Camera {
                 return Err("stream on [FAILED]".to_string());
             }
         }
-        self.enqueue_buffer();
+        self.streaming = true;
+        // the driver numbers frames from 0 again after stream on
+        self.last_sequence = None;
         Ok(true)
     }
 
-    pub fn stop_capture(&self) -> Result<bool, String> {
+    // Stream off also takes every buffer back from the driver
+    pub fn stop_capture(&mut self) -> Result<bool, String> {
         if !self.streaming {
             return Ok(true);
         }
@@ -353,42 +474,103 @@ impl Camera {
                 return Err("stream off [FAILED]".to_string());
             }
         }
+        self.streaming = false;
         Ok(true)
     }
-    // to capture an image
-    // send a buffer to the camera device
-    // wait for camera to fill the buffer
-    // read the buffer
+    // to capture a frame
+    // poll until the camera fills any queued buffer, or the timeout passes
+    // the buffer is the consumer's until the frame is dropped
 
-    pub fn capture_image(&self) -> Result<bool, String> {
+    pub fn capture_frame(&mut self) -> Result<Frame<'_>, CaptureError> {
         let start_time = Instant::now();
+        let media_fd = self.file.as_raw_fd();
+        let mut pollfd = libc::pollfd { fd: media_fd, events: libc::POLLIN, revents: 0 };
+        // far-off timeouts have no deadline an Instant can hold
+        let deadline = start_time.checked_add(self.timeout);
+        loop {
+            let remaining = deadline.map_or(self.timeout, |deadline| deadline.saturating_duration_since(Instant::now()));
+            let timeout_ms = remaining.as_millis().min(i32::MAX as u128) as i32;
+            match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+                0 => return Err(CaptureError::Timeout(self.timeout)),
+                ready if ready < 0 => match Errno::last() {
+                    // a signal cut the wait short, wait out the rest of it
+                    Errno::EINTR => continue,
+                    errno => return Err(CaptureError::Device(errno)),
+                },
+                _ => break,
+            }
+        }
 
         // #define VIDIOC_DQBUF		_IOWR('V', 17, struct v4l2_buffer)
         let mut buf: v4l2_buffer = unsafe { std::mem::zeroed() };
         buf.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
         buf.memory = v4l2_memory_V4L2_MEMORY_MMAP;
-        buf.index = 0;
-
-        let media_fd = self.file.as_raw_fd();
 
         ioctl_readwrite!(vidioc_dqbuf, VIDIOC_QUERYCAP_MAGIC, 17, v4l2_buffer);
         match unsafe { vidioc_dqbuf(media_fd, &mut buf) } {
             Ok(_) => {
-                info!("IOCTL: dqbuf [OK]");
+                info!("IOCTL: dqbuf [OK]: buffer {}", buf.index);
             }
-            Err(e) => {
+            // POLLERR with nothing to dequeue: not streaming, or no buffer queued
+            Err(Errno::EAGAIN) if pollfd.revents & (libc::POLLERR | libc::POLLHUP) != 0 => {
+                return Err(CaptureError::Device(Errno::EIO));
+            }
+            Err(Errno::EAGAIN) => return Err(CaptureError::WouldBlock),
+            Err(errno) => {
                 let elapsed = start_time.elapsed();
                 error!("Time at dqbuf failure: {} ms", elapsed.as_millis());
-                return Err("dqbuf [FAILED]".to_string());
+                return Err(CaptureError::Device(errno));
             }
         }
-        // enqueue buffer
-        self.enqueue_buffer();
 
         let elapsed = start_time.elapsed();
-        println!("Time after enqueueing buffer: {} ms", elapsed.as_millis());
+        debug!("Time after dequeueing buffer: {} ms", elapsed.as_millis());
 
-        Ok(true)
+        let timestamp = self.record_frame(&buf);
+        // compressed formats fill a different amount of the buffer every frame
+        Ok(Frame::new(self, buf.index, buf.bytesused as usize, timestamp, buf.sequence))
+    }
+
+    /** Counts the frames lost before a dequeued buffer and returns its capture time.
+    ** The driver numbers every frame it captures, also the ones it had no free
+    ** buffer for, so a jump forward in the numbering counts the frames lost in between.
+    ** Timestamps only mean capture time when the driver says they are monotonic;
+    ** otherwise the time of dequeueing is the best we have.
+    **/
+    fn record_frame(&mut self, buf: &v4l2_buffer) -> u64 {
+        if let Some(last) = self.last_sequence {
+            let dropped = match buf.sequence.checked_sub(last) {
+                // repeats come from drivers that leave the sequence at 0
+                Some(0) => 0,
+                Some(step) => step - 1,
+                // counting on past u32::MAX wraps around to small numbers
+                None if last - buf.sequence > u32::MAX / 2 => buf.sequence.wrapping_sub(last).wrapping_sub(1),
+                // anything else going backwards is the driver starting over
+                None => 0,
+            };
+            if dropped > 0 {
+                warn!("dropped {} frames before frame {}", dropped, buf.sequence);
+                self.dropped_frames += dropped as u64;
+            }
+        }
+        self.last_sequence = Some(buf.sequence);
+
+        match buf.flags & V4L2_BUF_FLAG_TIMESTAMP_MASK {
+            V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC => buf.timestamp.tv_sec as u64 * 1_000_000 + buf.timestamp.tv_usec as u64,
+            _ => monotonic_micros(),
+        }
+    }
+
+    // Whether the device still answers; an unplugged one fails every ioctl with ENODEV
+    pub fn is_connected(&self) -> bool {
+        let mut info: v4l2_capability = unsafe { std::mem::zeroed() };
+        ioctl_read!(vidioc_querycap, VIDIOC_QUERYCAP_MAGIC, VIDIOC_QUERYCAP_TYPE_MODE, v4l2_capability);
+        !matches!(unsafe { vidioc_querycap(self.file.as_raw_fd(), &mut info) }, Err(Errno::ENODEV))
+    }
+
+    // How long capture_frame waits before reporting CaptureError::Timeout
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
     }
 }
 
@@ -396,46 +578,95 @@ use std::time::Instant;
 use memmap::MmapMut;
 
 impl Camera { // helper functions
-    pub fn enqueue_buffer(&self) -> Result<bool, String> {
-        let media_fd = self.file.as_raw_fd();
-        // #define VIDIOC_QBUF		_IOWR('V', 15, struct v4l2_buffer)
-        let mut buf: v4l2_buffer = unsafe { std::mem::zeroed() };
-        buf.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
-        buf.memory = v4l2_memory_V4L2_MEMORY_MMAP;
-        buf.index = 0;
-
-        ioctl_readwrite!(vidioc_qbuf, VIDIOC_QUERYCAP_MAGIC, 15, v4l2_buffer);
-
-        match unsafe { vidioc_qbuf(media_fd, &mut buf) } {
+    fn enqueue_buffer(&self, index: u32) -> Result<bool, String> {
+        match self.queue(index) {
             Ok(_) => {
                 info!("IOCTL: qbuf [OK]");
             }
             Err(e) => {
-                return Err("qbuf [FAILED]".to_string());
+                return Err(format!("qbuf {} [FAILED]: {}", index, e));
             }
         }
         Ok(true)
     }
 
+    fn queue(&self, index: u32) -> Result<(), Errno> {
+        let media_fd = self.file.as_raw_fd();
+        // #define VIDIOC_QBUF		_IOWR('V', 15, struct v4l2_buffer)
+        let mut buf: v4l2_buffer = unsafe { std::mem::zeroed() };
+        buf.type_ = v4l2_buf_type_V4L2_BUF_TYPE_VIDEO_CAPTURE;
+        buf.memory = v4l2_memory_V4L2_MEMORY_MMAP;
+        buf.index = index;
+
+        ioctl_readwrite!(vidioc_qbuf, VIDIOC_QUERYCAP_MAGIC, 15, v4l2_buffer);
+        unsafe { vidioc_qbuf(media_fd, &mut buf) }.map(|_| ())
+    }
+
 }
 
 impl Camera {
-    pub fn get_buffer_ptr(&self) -> *const u8 {
-        self.buffer.buffer as *const u8
+    // Frames the driver captured but we never saw, since the camera was opened
+    pub fn get_dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    // Everything the driver accepted during negotiation
+    pub fn get_format(&self) -> CaptureFormat {
+        self.format
     }
 
-    pub fn get_buffer_length(&self) -> usize {
-        self.buffer.length
+    // For cameras that report one colorimetry and send another
+    pub fn set_colorimetry(&mut self, colorimetry: Colorimetry) {
+        self.format.colorimetry = colorimetry;
     }
 }
 
+/** Maps the colorspace, ycbcr_enc and quantization fields of a V4L2 format to a
+** Colorimetry. Drivers may leave the last two at DEFAULT, which V4L2 defines in
+** terms of the colorspace; this follows the kernel's V4L2_MAP_*_DEFAULT macros.
+** BT.2020 and SMPTE 240M have no kernels of their own and use BT.709, the
+** closest matrix we have.
+**/
+#[allow(non_upper_case_globals)]
+fn colorimetry_of(format: &v4l2_pix_format) -> Colorimetry {
+    let ycbcr_enc = match unsafe { format.__bindgen_anon_1.ycbcr_enc } {
+        v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_DEFAULT => match format.colorspace {
+            v4l2_colorspace_V4L2_COLORSPACE_REC709 | v4l2_colorspace_V4L2_COLORSPACE_DCI_P3 => v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_709,
+            v4l2_colorspace_V4L2_COLORSPACE_BT2020 => v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_BT2020,
+            v4l2_colorspace_V4L2_COLORSPACE_SMPTE240M => v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_SMPTE240M,
+            _ => v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_601,
+        },
+        ycbcr_enc => ycbcr_enc,
+    };
+    let matrix = match ycbcr_enc {
+        v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_601 | v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_XV601 => YuvMatrix::Bt601,
+        v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_709 | v4l2_ycbcr_encoding_V4L2_YCBCR_ENC_XV709 => YuvMatrix::Bt709,
+        other => {
+            warn!("ycbcr_enc {} has no conversion of its own, using BT.709", other);
+            YuvMatrix::Bt709
+        }
+    };
+
+    let range = match format.quantization {
+        v4l2_quantization_V4L2_QUANTIZATION_FULL_RANGE => YuvRange::Full,
+        v4l2_quantization_V4L2_QUANTIZATION_LIM_RANGE => YuvRange::Limited,
+        // YCbCr defaults to limited range, except in the JPEG colorspace
+        _ if format.colorspace == v4l2_colorspace_V4L2_COLORSPACE_JPEG => YuvRange::Full,
+        _ => YuvRange::Limited,
+    };
+
+    Colorimetry { matrix, range }
+}
+
 impl Drop for Camera {
     fn drop(&mut self) {
         // dequeue buffer
         self.stop_capture();
-        // unmap buffer
-        unsafe {
-            libc::munmap(self.buffer.buffer, self.buffer.length);
+        // unmap buffers
+        for buffer in &self.buffers {
+            unsafe {
+                libc::munmap(buffer.buffer, buffer.length);
+            }
         }
 
     }
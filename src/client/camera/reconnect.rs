@@ -0,0 +1,155 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+use crate::client::camera::{list_cameras, Camera, CameraInfo, CaptureError, CaptureRequest};
+use crate::convert::Colorimetry;
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, SourceError, SourceStatus};
+use crate::types::Image;
+
+// Everything a camera is opened with, so it can be opened again the same way
+#[derive(Debug, Clone)]
+pub struct CameraSetup {
+    pub request: CaptureRequest,
+    pub buffer_count: u32,
+    pub timeout: Duration,
+    // overrides what the camera reports
+    pub colorimetry: Option<Colorimetry>,
+    // control presets, as parse_presets returns them
+    pub controls: Vec<(String, String)>,
+}
+
+impl CameraSetup {
+    // Opens and configures the camera at `path`, it is not streaming yet
+    pub fn open(&self, path: &Path) -> Result<Camera, String> {
+        let mut camera = Camera::new(path, &self.request, self.buffer_count)?;
+        camera.set_timeout(self.timeout);
+        if let Some(colorimetry) = self.colorimetry {
+            camera.set_colorimetry(colorimetry);
+        }
+        camera.apply_controls(&self.controls)?;
+        Ok(camera)
+    }
+}
+
+/** Whether `candidate` is the same physical camera as `camera`. The by-id links
+** carry the USB serial number where the camera has one, so they follow it to
+** any port; without them it has to come back on the same bus position.
+**/
+fn same_camera(camera: &CameraInfo, candidate: &CameraInfo) -> bool {
+    if !camera.by_id.is_empty() && !candidate.by_id.is_empty() {
+        return camera.by_id.iter().any(|link| candidate.by_id.contains(link));
+    }
+    camera.bus_info == candidate.bus_info && camera.card == candidate.card
+}
+
+/** A camera that survives being unplugged. When it is lost, the Camera is
+** dropped, which stops streaming and unmaps its buffers; no Frame can still
+** point into them, as frames borrow the camera. Then every `retry_interval`
+** the cameras are scanned for the same one, which is reopened with the format
+** negotiated the first time and the same controls. Meanwhile next_frame waits
+** like a stalled camera would and status reports Disconnected.
+**/
+pub struct ReconnectingCamera {
+    camera: Option<Camera>,
+    identity: CameraInfo,
+    setup: CameraSetup,
+    retry_interval: Duration,
+    last_attempt: Instant,
+    // frames dropped by the cameras opened before the current one
+    dropped_before: u64,
+}
+
+impl ReconnectingCamera {
+    // `camera` must be streaming already, opened from `identity` with `setup`
+    pub fn new(camera: Camera, identity: CameraInfo, mut setup: CameraSetup, retry_interval: Duration) -> ReconnectingCamera {
+        // ask for exactly what was negotiated, so the camera comes back the same
+        let format = camera.get_format();
+        setup.request = CaptureRequest {
+            pixel_format: Some(format.pixel_format),
+            width: format.width,
+            height: format.height,
+            // drivers that report no interval keep the requested rate
+            fps: match format.interval.fps().round() as u32 {
+                0 => setup.request.fps,
+                fps => fps,
+            },
+        };
+        ReconnectingCamera { camera: Some(camera), identity, setup, retry_interval, last_attempt: Instant::now(), dropped_before: 0 }
+    }
+
+    fn disconnect(&mut self, e: CaptureError) {
+        if let Some(camera) = self.camera.take() {
+            self.dropped_before += camera.get_dropped_frames();
+        }
+        warn!("camera {} disconnected ({}), retrying every {:?}", self.identity.card, e, self.retry_interval);
+        self.last_attempt = Instant::now();
+    }
+
+    fn reconnect(&mut self) -> Result<(), String> {
+        let candidate = list_cameras()?
+            .into_iter()
+            .find(|candidate| same_camera(&self.identity, candidate))
+            .ok_or("not found")?;
+        let mut camera = self.setup.open(&candidate.path)?;
+        camera.start_capture()?;
+
+        let (format, request) = (camera.get_format(), &self.setup.request);
+        if Some(format.pixel_format) != request.pixel_format || (format.width, format.height) != (request.width, request.height) {
+            warn!("camera came back as {:?} {}x{}", format.pixel_format, format.width, format.height);
+        }
+        info!("camera {} reconnected at {}", candidate.card, candidate.path.display());
+        // the node may have a different index now
+        self.identity = candidate;
+        self.camera = Some(camera);
+        Ok(())
+    }
+}
+
+impl FrameSource for ReconnectingCamera {
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        let Some(camera) = &mut self.camera else {
+            let due = self.last_attempt + self.retry_interval;
+            let now = Instant::now();
+            if due > now {
+                thread::sleep((due - now).min(self.setup.timeout));
+                return Ok(None);
+            }
+            self.last_attempt = now;
+            if let Err(e) = self.reconnect() {
+                info!("camera {} is not back yet: {}", self.identity.card, e);
+            }
+            return Ok(None);
+        };
+
+        match camera.next_image(pool) {
+            Ok(image) => Ok(image),
+            // a queue that errors out right at unplug may not say ENODEV itself
+            Err(e) if e.is_disconnect() || !camera.is_connected() => {
+                self.disconnect(e);
+                Ok(None)
+            }
+            Err(e) => Err(SourceError::Failed(e.to_string())),
+        }
+    }
+
+    fn name(&self) -> String {
+        match &self.camera {
+            Some(camera) => camera.name(),
+            None => format!("{} (disconnected)", self.identity.path.display()),
+        }
+    }
+
+    fn dropped_frames(&self) -> u64 {
+        self.dropped_before + self.camera.as_ref().map_or(0, |camera| camera.get_dropped_frames())
+    }
+
+    fn status(&self) -> SourceStatus {
+        match self.camera {
+            Some(_) => SourceStatus::Live,
+            None => SourceStatus::Disconnected,
+        }
+    }
+}
@@ -8,11 +8,16 @@ use structopt::StructOpt;
 use client::app::App;
 use client::server_client::ServerClient;
 use crate::client;
-use crate::types::Arguments;
+use crate::types::{Arguments, PixelFormat, SourceSpec};
+use crate::source::{FileSource, FrameSource, OpenCvSource, ReplaySource, SourceError, SyntheticSource};
+use crate::record::Recorder;
 
-use client::camera::Camera;
+use client::camera::{find_camera, parse_presets, Camera, CameraInfo, CameraSetup, CaptureRequest, ReconnectingCamera};
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
 
-pub fn run_client() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_client() -> Result<(), Box<dyn Error>> {
 
     let opt = Arguments::from_args();
     
@@ -21,22 +26,101 @@ pub fn run_client() -> Result<(), Box<dyn std::error::Error>> {
     // videoio::VideoCapture::is_opened(&cam).expect("Open camera [FAILED]");
     // cam.set(CAP_PROP_FPS, 30.0).expect("Set camera FPS [FAILED]");
 
-    let mut cam = Camera::new(0);
-    cam.set_frame_rate(30).expect("Set camera FPS [FAILED]");
-    cam.start_capture().expect("Start camera capture [FAILED]");
+    if opt.list_controls {
+        for control in open_camera(&opt)?.0.list_controls()? {
+            println!("{}", control);
+        }
+        return Ok(());
+    }
+    if let Some(name) = &opt.get_control {
+        println!("{} = {}", name, open_camera(&opt)?.0.get_control_by_name(name)?);
+        return Ok(());
+    }
+    if !opt.set_control.is_empty() {
+        let (camera, _, _) = open_camera(&opt)?;
+        for assignment in &opt.set_control {
+            let presets = parse_presets(assignment).map_err(|e| format!("--set-control {}: {}", assignment, e))?;
+            for (name, value) in presets {
+                camera.set_control_by_name(&name, &value)?;
+            }
+        }
+        return Ok(());
+    }
+    let source = open_source(&opt, SourceSpec::V4l2)?;
+    println!("Using source {}", source.name());
 
 
 
     let server_client = ServerClient::new(opt.connect.as_str());
-    let mut app = App::new(server_client, cam);
+    let mut app = App::new(server_client, source, opt.rotation, opt.mirror, opt.swap_sides, opt.display_converter);
+    if let Some(path) = &opt.record {
+        app.record_to(Recorder::create(Path::new(path))?);
+        println!("Recording to {}", path);
+    }
 
 
     loop {
-        app.process_frame();
+        // a stalled camera only skips the frame, the window keeps handling keys
+        match app.process_frame() {
+            Ok(()) => {}
+            Err(SourceError::Ended) => break,
+            Err(e) => return Err(e.into()),
+        }
         let key = wait_key(1).unwrap();
         if key > 0 && key != 255 {
             break;
         }
     }
+    if let Some(frames) = app.finish_recording()? {
+        println!("Recorded {} frames", frames);
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+// The V4L2 camera --camera selects, set up from the arguments but not yet streaming
+fn open_camera(opt: &Arguments) -> Result<(Camera, CameraInfo, CameraSetup), Box<dyn Error>> {
+    let request = CaptureRequest {
+        pixel_format: opt.pixel_format,
+        width: opt.width,
+        height: opt.height,
+        fps: opt.fps,
+    };
+    // e.g. manual exposure and fixed focus, so fast movement does not blur
+    let controls = match &opt.controls {
+        Some(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("read {} [FAILED]: {}", path, e))?;
+            parse_presets(&text).map_err(|e| format!("{}: {}", path, e))?
+        }
+        None => Vec::new(),
+    };
+    let setup = CameraSetup {
+        request,
+        buffer_count: opt.buffers,
+        timeout: Duration::from_millis(opt.capture_timeout),
+        colorimetry: opt.colorimetry,
+        controls,
+    };
+    let camera = find_camera(&opt.camera)?;
+    println!("Using camera {}", camera);
+    let cam = setup.open(&camera.path)?;
+    Ok((cam, camera, setup))
+}
+
+// The frame source --source asks for, `default` when it is not given
+pub fn open_source(opt: &Arguments, default: SourceSpec) -> Result<Box<dyn FrameSource>, Box<dyn Error>> {
+    let source: Box<dyn FrameSource> = match opt.source.clone().unwrap_or(default) {
+        SourceSpec::V4l2 => {
+            let (mut cam, camera, setup) = open_camera(opt)?;
+            cam.start_capture()?;
+            Box::new(ReconnectingCamera::new(cam, camera, setup, Duration::from_millis(opt.reconnect_interval)))
+        }
+        SourceSpec::OpenCv => Box::new(OpenCvSource::open(&opt.camera, opt.fps)?),
+        SourceSpec::File(path) => Box::new(FileSource::new(Path::new(&path), opt.fps, opt.repeat)?),
+        SourceSpec::Replay(path) => Box::new(ReplaySource::open(Path::new(&path), opt.replay_timing, opt.repeat)?),
+        SourceSpec::Synthetic => {
+            let pixel_format = opt.pixel_format.unwrap_or(PixelFormat::YUYV);
+            Box::new(SyntheticSource::new(opt.width, opt.height, pixel_format, opt.fps)?)
+        }
+    };
+    Ok(source)
+}
@@ -1,9 +1,17 @@
-
+#[cfg(feature = "opencv")]
 mod app;
+#[cfg(feature = "opencv")]
 mod server_client;
+#[cfg(feature = "opencv")]
 mod client_main;
+// discovery needs only V4L2, so --list-cameras works without opencv
+#[cfg_attr(not(feature = "opencv"), allow(dead_code))]
 mod camera;
 
+#[cfg(feature = "opencv")]
 pub use app::App;
+#[cfg(feature = "opencv")]
 pub use server_client::ServerClient;
-pub use client_main::run_client;
+#[cfg(feature = "opencv")]
+pub use client_main::{open_source, run_client};
+pub use camera::list_cameras;
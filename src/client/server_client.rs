@@ -7,15 +7,17 @@ use prost::Message;
 use crate::proto::DnnRequest;
 use crate::proto::DnnResponse;
 use crate::types::InferenceResults;
-use std::time::{SystemTime, UNIX_EPOCH};
 use std::net::TcpStream;
 use std::io::{Write, Read};
 use crate::utils::*;
 use crate::types::*;
+use crate::convert::{YuvMatrix, YuvRange};
+use crate::pool::BufferPool;
 
 pub struct ServerClient {
     server_address: String,
-    stream: TcpStream
+    stream: TcpStream,
+    pool: BufferPool
 }
 
 impl ServerClient {
@@ -27,7 +29,8 @@ impl ServerClient {
 
         ServerClient {
             server_address: server_address.to_string(),
-            stream: stream
+            stream: stream,
+            pool: BufferPool::new(2)
         }
     }
 
@@ -39,22 +42,34 @@ impl ServerClient {
 
     pub fn send_image_and_get_results(&mut self, image: &Mat) -> InferenceResults {
         let serialized_image= Image::from_mat(image);
-        let serialized_image = resize_with_padding_ultra_fast(&serialized_image, (192, 192), COLOR_SPACE::RGB);
+        let serialized_image = resize_with_padding_ultra_fast(&serialized_image, (192, 192), ResampleMode::Nearest, &self.pool);
         self.send_data_image(&serialized_image);
+        self.pool.recycle(serialized_image);
         self.receive_results()
     }
 
     pub fn send_data_image(&mut self, image: &Image) {
-        self.send_data(&image.data, image.width as u32, image.height as u32);
+        self.send_data(&image.data, &image.frame_info(Rotation::None));
     }
-    pub fn send_data(&mut self, data: &[u8], width: u32, col: u32) {
+
+    // The timestamp and sequence of `frame` identify it, the server echoes them with its results
+    pub fn send_data(&mut self, data: &[u8], frame: &FrameInfo) {
         println!("Sending data to server...");
-        println!("size of data: {}, width: {}, height: {}", data.len(), width, col);
+        println!("size of data: {}, width: {}, height: {}", data.len(), frame.width, frame.height);
         let dnn_request = DnnRequest {
             image_num_bytes: data.len() as u64,
-            width: width,
-            height: col,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+            width: frame.width,
+            height: frame.height,
+            pixel_format: frame.pixel_format.fourcc(),
+            stride: frame.stride as u32,
+            rotation: frame.rotation.degrees(),
+            yuv_matrix: match frame.colorimetry.matrix {
+                YuvMatrix::Bt601 => 601,
+                YuvMatrix::Bt709 => 709,
+            },
+            limited_range: frame.colorimetry.range == YuvRange::Limited,
+            timestamp: frame.timestamp,
+            sequence: frame.sequence,
         };
 
         let mut dnn_request_buf = Vec::new();
@@ -81,6 +96,7 @@ impl ServerClient {
 
         InferenceResults {
             timestamp: response.timestamp,
+            sequence: response.sequence,
             vector: response.vector.clone()
         }
     }
@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use super::{available_backends, bgr_to_yuyv_with, yuyv_to_bgr_with, Backend, Colorimetry, YuvMatrix, YuvRange, BT601_FULL};
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 480;
+const ITERATIONS: u32 = 200;
+
+/** Times every available backend on a 640x480 frame and checks that each one
+** matches the scalar reference byte for byte, for every colorimetry. Single-threaded,
+** so the numbers show the per-core speedup.
+**/
+pub fn run_benchmark() -> Result<(), String> {
+    // deterministic noise so every code path and clamp gets exercised
+    let mut state: u32 = 0x1234_5678;
+    let yuyv: Vec<u8> = (0..WIDTH * HEIGHT * 2)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 24) as u8
+        })
+        .collect();
+    let mut bgr = vec![0u8; WIDTH * HEIGHT * 3];
+    yuyv_to_bgr_with(Backend::Scalar, &BT601_FULL, &yuyv, &mut bgr);
+    let mut reference_yuyv = vec![0u8; yuyv.len()];
+    bgr_to_yuyv_with(Backend::Scalar, &BT601_FULL, &bgr, &mut reference_yuyv);
+
+    if *Colorimetry::BT601_FULL.coefficients() != BT601_FULL {
+        return Err("derived BT.601 full range coefficients differ from BT601_FULL".to_string());
+    }
+
+    println!("YUYV <-> BGR24 conversion, {}x{}, {} iterations", WIDTH, HEIGHT, ITERATIONS);
+    let mut scalar_times = (0.0, 0.0);
+    for backend in available_backends() {
+        let mut out_bgr = vec![0u8; bgr.len()];
+        let mut out_yuyv = vec![0u8; yuyv.len()];
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            yuyv_to_bgr_with(backend, &BT601_FULL, &yuyv, &mut out_bgr);
+        }
+        let to_bgr = start.elapsed().as_secs_f64() * 1000.0 / ITERATIONS as f64;
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            bgr_to_yuyv_with(backend, &BT601_FULL, &bgr, &mut out_yuyv);
+        }
+        let to_yuyv = start.elapsed().as_secs_f64() * 1000.0 / ITERATIONS as f64;
+
+        if out_bgr != bgr || out_yuyv != reference_yuyv {
+            return Err(format!("{:?} output differs from the scalar reference", backend));
+        }
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709] {
+            for range in [YuvRange::Full, YuvRange::Limited] {
+                let coefficients = Colorimetry { matrix, range }.coefficients();
+                let mut expected = vec![0u8; bgr.len()];
+                yuyv_to_bgr_with(Backend::Scalar, coefficients, &yuyv, &mut expected);
+                yuyv_to_bgr_with(backend, coefficients, &yuyv, &mut out_bgr);
+                let mut expected_yuyv = vec![0u8; yuyv.len()];
+                bgr_to_yuyv_with(Backend::Scalar, coefficients, &bgr, &mut expected_yuyv);
+                bgr_to_yuyv_with(backend, coefficients, &bgr, &mut out_yuyv);
+                if out_bgr != expected || out_yuyv != expected_yuyv {
+                    return Err(format!("{:?} output differs from the scalar reference for {:?} {:?}", backend, matrix, range));
+                }
+            }
+        }
+        if backend == Backend::Scalar {
+            scalar_times = (to_bgr, to_yuyv);
+        }
+        println!(
+            "  {:<7} yuyv->bgr {:7.3} ms ({:4.1}x)   bgr->yuyv {:7.3} ms ({:4.1}x)   [bit-identical]",
+            format!("{:?}", backend),
+            to_bgr, scalar_times.0 / to_bgr,
+            to_yuyv, scalar_times.1 / to_yuyv
+        );
+    }
+    Ok(())
+}
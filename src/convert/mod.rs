@@ -0,0 +1,337 @@
+/*
+ * Fixed-point YUV 4:2:2 (YUYV) <-> BGR24 conversion.
+ *
+ * Every backend implements the same integer reference, so the scalar, SSE2,
+ * AVX2 and NEON paths produce bit-identical output. Coefficients are stored in
+ * Q13 (value * 8192, rounded to the nearest integer) and all sums are exact in
+ * i32. `>>` below is an arithmetic shift and clamp() saturates to [0, 255].
+ *
+ * YUYV -> BGR, for each pixel with luma y and the chroma u, v of its macropixel:
+ *     yd = y_gain * (y - y_offset)
+ *     r  = clamp((yd + rv * (v - 128) + 4096) >> 13)
+ *     g  = clamp((yd + gu * (u - 128) + gv * (v - 128) + 4096) >> 13)
+ *     b  = clamp((yd + bu * (u - 128) + 4096) >> 13)
+ *
+ * BGR -> YUYV, for each pixel (chroma is taken from the first pixel of a pair):
+ *     y = clamp(((yr * r + yg * g + yb * b + 4096) >> 13) + y_offset)
+ *     u = clamp(((ur * r + ug * g + ub * b + 4096) >> 13) + 128)
+ *     v = clamp(((vr * r + vg * g + vb * b + 4096) >> 13) + 128)
+ *
+ * The coefficients follow from the matrix (luma weights kr, kb, kg = 1 - kr - kb)
+ * and the range (full: y in 0..=255, limited: y in 16..=235, chroma 16..=240):
+ *     y_offset = 0 or 16, ys = 1 or 219 / 255, cs = 1 or 224 / 255
+ *     y_gain = 1 / ys, rv = 2 (1 - kr) / cs, bu = 2 (1 - kb) / cs
+ *     gu = -2 kb (1 - kb) / (kg cs), gv = -2 kr (1 - kr) / (kg cs)
+ *     yr, yb = kr ys, kb ys,  ur = -kr cs / (2 (1 - kb)),  ub = vr = cs / 2
+ *     vb = -kb cs / (2 (1 - kr))
+ * and yg, ug, vg are whatever keeps each row summing to ys, 0 and 0, so white
+ * and greys convert exactly.
+ */
+
+pub mod scalar;
+mod bench;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86;
+#[cfg(target_arch = "aarch64")]
+mod neon;
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+pub use bench::run_benchmark;
+
+pub const SHIFT: i32 = 13;
+pub const ROUND: i32 = 1 << (SHIFT - 1);
+
+// Q13 conversion coefficients, see the reference above
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YuvCoefficients {
+    pub y_offset: i16,
+    // YUV -> RGB
+    pub y_gain: i16,
+    pub rv: i16,
+    pub gu: i16,
+    pub gv: i16,
+    pub bu: i16,
+    // RGB -> YUV
+    pub yr: i16,
+    pub yg: i16,
+    pub yb: i16,
+    pub ur: i16,
+    pub ug: i16,
+    pub ub: i16,
+    pub vr: i16,
+    pub vg: i16,
+    pub vb: i16,
+}
+
+// BT.601 full range (JFIF), what the camera code has always assumed
+pub const BT601_FULL: YuvCoefficients = YuvCoefficients {
+    y_offset: 0,
+    y_gain: 8192,
+    rv: 11485,
+    gu: -2819,
+    gv: -5850,
+    bu: 14516,
+    yr: 2449,
+    yg: 4809,
+    yb: 934,
+    ur: -1382,
+    ug: -2714,
+    ub: 4096,
+    vr: 4096,
+    vg: -3430,
+    vb: -666,
+};
+
+// Which luma weights the camera encoded with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YuvMatrix {
+    Bt601,
+    Bt709
+}
+
+impl YuvMatrix {
+    // (kr, kb)
+    fn weights(&self) -> (f64, f64) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YuvRange {
+    // 0..=255, as in JPEG
+    Full,
+    // 16..=235 luma and 16..=240 chroma, as broadcast video and most HD cameras
+    Limited
+}
+
+// How YUV samples of an image map to RGB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Colorimetry {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange
+}
+
+impl Colorimetry {
+    pub const BT601_FULL: Colorimetry = Colorimetry { matrix: YuvMatrix::Bt601, range: YuvRange::Full };
+
+    // The Q13 coefficients, computed once per combination
+    pub fn coefficients(&self) -> &'static YuvCoefficients {
+        static TABLE: OnceLock<[YuvCoefficients; 4]> = OnceLock::new();
+        let table = TABLE.get_or_init(|| [
+            YuvCoefficients::new(YuvMatrix::Bt601, YuvRange::Full),
+            YuvCoefficients::new(YuvMatrix::Bt601, YuvRange::Limited),
+            YuvCoefficients::new(YuvMatrix::Bt709, YuvRange::Full),
+            YuvCoefficients::new(YuvMatrix::Bt709, YuvRange::Limited),
+        ]);
+        &table[(self.matrix as usize) * 2 + self.range as usize]
+    }
+}
+
+// What the code has always assumed when nothing else is known
+impl Default for Colorimetry {
+    fn default() -> Self {
+        Colorimetry::BT601_FULL
+    }
+}
+
+impl FromStr for Colorimetry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (matrix, range) = match s.to_lowercase().as_str() {
+            "bt601-full" => (YuvMatrix::Bt601, YuvRange::Full),
+            "bt601-limited" => (YuvMatrix::Bt601, YuvRange::Limited),
+            "bt709-full" => (YuvMatrix::Bt709, YuvRange::Full),
+            "bt709-limited" => (YuvMatrix::Bt709, YuvRange::Limited),
+            _ => return Err(format!("unknown colorimetry '{}', expected bt601-full, bt601-limited, bt709-full or bt709-limited", s)),
+        };
+        Ok(Colorimetry { matrix, range })
+    }
+}
+
+impl YuvCoefficients {
+    // Derives the coefficients from the formulas at the top of this file
+    pub fn new(matrix: YuvMatrix, range: YuvRange) -> YuvCoefficients {
+        let (kr, kb) = matrix.weights();
+        let kg = 1.0 - kr - kb;
+        let (y_offset, ys, cs) = match range {
+            YuvRange::Full => (0, 1.0, 1.0),
+            YuvRange::Limited => (16, 219.0 / 255.0, 224.0 / 255.0),
+        };
+        let q = |value: f64| (value * (1 << SHIFT) as f64).round() as i16;
+
+        let (yr, yb) = (q(kr * ys), q(kb * ys));
+        let (ur, ub) = (q(-kr * cs / (2.0 * (1.0 - kb))), q(cs / 2.0));
+        let (vr, vb) = (q(cs / 2.0), q(-kb * cs / (2.0 * (1.0 - kr))));
+        YuvCoefficients {
+            y_offset,
+            y_gain: q(1.0 / ys),
+            rv: q(2.0 * (1.0 - kr) / cs),
+            gu: q(-2.0 * kb * (1.0 - kb) / (kg * cs)),
+            gv: q(-2.0 * kr * (1.0 - kr) / (kg * cs)),
+            bu: q(2.0 * (1.0 - kb) / cs),
+            yr,
+            yg: q(ys) - yr - yb,
+            yb,
+            ur,
+            ug: -ur - ub,
+            ub,
+            vr,
+            vg: -vr - vb,
+            vb,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Scalar,
+    Sse2,
+    Avx2,
+    Neon,
+}
+
+impl Backend {
+    pub fn is_available(&self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Backend::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+// Every backend this CPU can run, slowest first
+pub fn available_backends() -> Vec<Backend> {
+    [Backend::Scalar, Backend::Sse2, Backend::Avx2, Backend::Neon]
+        .into_iter()
+        .filter(|backend| backend.is_available())
+        .collect()
+}
+
+// The fastest available backend, detected once
+pub fn backend() -> Backend {
+    static BACKEND: OnceLock<Backend> = OnceLock::new();
+    *BACKEND.get_or_init(|| *available_backends().last().unwrap())
+}
+
+// Converts whole YUYV macropixels in `src` to BGR24 pixels in `dst`
+pub fn yuyv_to_bgr(coefficients: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    yuyv_to_bgr_with(backend(), coefficients, src, dst)
+}
+
+pub fn yuyv_to_bgr_with(backend: Backend, coefficients: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    assert!(backend.is_available(), "{:?} is not supported on this CPU", backend);
+    debug_assert_eq!(dst.len(), src.len() / 4 * 6, "Output buffer length must be 3/2 of input buffer length");
+
+    match backend {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Backend::Sse2 => unsafe { x86::yuyv_to_bgr_sse2(coefficients, src, dst) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Backend::Avx2 => unsafe { x86::yuyv_to_bgr_avx2(coefficients, src, dst) },
+        #[cfg(target_arch = "aarch64")]
+        Backend::Neon => unsafe { neon::yuyv_to_bgr_neon(coefficients, src, dst) },
+        _ => scalar::yuyv_to_bgr(coefficients, src, dst),
+    }
+}
+
+// Converts pairs of BGR24 pixels in `src` to YUYV macropixels in `dst`
+pub fn bgr_to_yuyv(coefficients: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    bgr_to_yuyv_with(backend(), coefficients, src, dst)
+}
+
+pub fn bgr_to_yuyv_with(backend: Backend, coefficients: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    assert!(backend.is_available(), "{:?} is not supported on this CPU", backend);
+    debug_assert_eq!(dst.len(), src.len() / 6 * 4, "Output buffer length must be 2/3 of input buffer length");
+
+    match backend {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Backend::Sse2 => unsafe { x86::bgr_to_yuyv_sse2(coefficients, src, dst) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Backend::Avx2 => unsafe { x86::bgr_to_yuyv_avx2(coefficients, src, dst) },
+        #[cfg(target_arch = "aarch64")]
+        Backend::Neon => unsafe { neon::bgr_to_yuyv_neon(coefficients, src, dst) },
+        _ => scalar::bgr_to_yuyv(coefficients, src, dst),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Macropixel counts around the 16 and 32 byte blocks, so every tail length gets run
+    const LENGTHS: [usize; 14] = [0, 1, 2, 3, 4, 5, 7, 8, 9, 12, 15, 17, 31, 161];
+
+    fn colorimetries() -> Vec<Colorimetry> {
+        [YuvMatrix::Bt601, YuvMatrix::Bt709]
+            .into_iter()
+            .flat_map(|matrix| [YuvRange::Full, YuvRange::Limited].map(|range| Colorimetry { matrix, range }))
+            .collect()
+    }
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state: u32 = 0x1234_5678;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    // Every combination of 0 and 255 over each group of four bytes, which drives the clamps
+    fn extremes(len: usize) -> Vec<u8> {
+        (0..len).map(|i| if (i / 4) >> (i % 4) & 1 == 1 { 255 } else { 0 }).collect()
+    }
+
+    fn inputs(len: usize) -> Vec<Vec<u8>> {
+        vec![noise(len), extremes(len), vec![0; len], vec![255; len]]
+    }
+
+    #[test]
+    fn yuyv_to_bgr_backends_match_scalar() {
+        for backend in available_backends() {
+            for colorimetry in colorimetries() {
+                let coefficients = colorimetry.coefficients();
+                for macropixels in LENGTHS {
+                    for yuyv in inputs(macropixels * 4) {
+                        let mut expected = vec![0u8; macropixels * 6];
+                        scalar::yuyv_to_bgr(coefficients, &yuyv, &mut expected);
+                        let mut bgr = vec![0u8; macropixels * 6];
+                        yuyv_to_bgr_with(backend, coefficients, &yuyv, &mut bgr);
+                        assert_eq!(bgr, expected, "{:?} {:?}, {} macropixels of {:?}", backend, colorimetry, macropixels, yuyv);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bgr_to_yuyv_backends_match_scalar() {
+        for backend in available_backends() {
+            for colorimetry in colorimetries() {
+                let coefficients = colorimetry.coefficients();
+                for macropixels in LENGTHS {
+                    for bgr in inputs(macropixels * 6) {
+                        let mut expected = vec![0u8; macropixels * 4];
+                        scalar::bgr_to_yuyv(coefficients, &bgr, &mut expected);
+                        let mut yuyv = vec![0u8; macropixels * 4];
+                        bgr_to_yuyv_with(backend, coefficients, &bgr, &mut yuyv);
+                        assert_eq!(yuyv, expected, "{:?} {:?}, {} macropixels of {:?}", backend, colorimetry, macropixels, bgr);
+                    }
+                }
+            }
+        }
+    }
+}
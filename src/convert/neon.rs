@@ -0,0 +1,113 @@
+use std::arch::aarch64::*;
+
+use super::{scalar, YuvCoefficients, ROUND, SHIFT};
+
+// (sum + ROUND) >> SHIFT, plus offset, saturated to u8
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn narrow(lo: int32x4_t, hi: int32x4_t, offset: i32) -> uint8x8_t {
+    let round = vdupq_n_s32(ROUND);
+    let offset = vdupq_n_s32(offset);
+    let lo = vaddq_s32(vshrq_n_s32(vaddq_s32(lo, round), SHIFT), offset);
+    let hi = vaddq_s32(vshrq_n_s32(vaddq_s32(hi, round), SHIFT), offset);
+    vqmovun_s16(vcombine_s16(vqmovn_s32(lo), vqmovn_s32(hi)))
+}
+
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn widen(x: uint8x8_t) -> int16x8_t {
+    vreinterpretq_s16_u16(vmovl_u8(x))
+}
+
+// 8 pixels of luma with their (already centered) chroma -> (b, g, r)
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn yuv_block(c: &YuvCoefficients, luma: uint8x8_t, u: int16x8_t, v: int16x8_t) -> (uint8x8_t, uint8x8_t, uint8x8_t) {
+    let yd = vsubq_s16(widen(luma), vdupq_n_s16(c.y_offset));
+    let y_lo = vmull_n_s16(vget_low_s16(yd), c.y_gain);
+    let y_hi = vmull_high_n_s16(yd, c.y_gain);
+
+    let r = narrow(
+        vmlal_n_s16(y_lo, vget_low_s16(v), c.rv),
+        vmlal_high_n_s16(y_hi, v, c.rv),
+        0,
+    );
+    let g = narrow(
+        vmlal_n_s16(vmlal_n_s16(y_lo, vget_low_s16(u), c.gu), vget_low_s16(v), c.gv),
+        vmlal_high_n_s16(vmlal_high_n_s16(y_hi, u, c.gu), v, c.gv),
+        0,
+    );
+    let b = narrow(
+        vmlal_n_s16(y_lo, vget_low_s16(u), c.bu),
+        vmlal_high_n_s16(y_hi, u, c.bu),
+        0,
+    );
+    (b, g, r)
+}
+
+// cr * r + cg * g + cb * b for 8 pixels, narrowed with `offset`
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn dot(r: int16x8_t, g: int16x8_t, b: int16x8_t, (cr, cg, cb): (i16, i16, i16), offset: i32) -> uint8x8_t {
+    let lo = vmlal_n_s16(vmlal_n_s16(vmull_n_s16(vget_low_s16(r), cr), vget_low_s16(g), cg), vget_low_s16(b), cb);
+    let hi = vmlal_high_n_s16(vmlal_high_n_s16(vmull_high_n_s16(r, cr), g, cg), b, cb);
+    narrow(lo, hi, offset)
+}
+
+// 8 pixels of (r, g, b) -> (y, u, v)
+#[inline]
+#[target_feature(enable = "neon")]
+unsafe fn rgb_block(c: &YuvCoefficients, r: uint8x8_t, g: uint8x8_t, b: uint8x8_t) -> (uint8x8_t, uint8x8_t, uint8x8_t) {
+    let (r, g, b) = (widen(r), widen(g), widen(b));
+    (
+        dot(r, g, b, (c.yr, c.yg, c.yb), c.y_offset as i32),
+        dot(r, g, b, (c.ur, c.ug, c.ub), 128),
+        dot(r, g, b, (c.vr, c.vg, c.vb), 128),
+    )
+}
+
+// 16 pixels per iteration, vld4/vst3 do the (de)interleaving
+#[target_feature(enable = "neon")]
+pub unsafe fn yuyv_to_bgr_neon(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 32;
+    let chroma_offset = vdupq_n_s16(128);
+
+    for block in 0..blocks {
+        // even lumas, U, odd lumas, V of 8 macropixels
+        let px = vld4_u8(src.as_ptr().add(block * 32));
+        let u = vsubq_s16(widen(px.1), chroma_offset);
+        let v = vsubq_s16(widen(px.3), chroma_offset);
+
+        let even = yuv_block(c, px.0, u, v);
+        let odd = yuv_block(c, px.2, u, v);
+
+        let b = vzip_u8(even.0, odd.0);
+        let g = vzip_u8(even.1, odd.1);
+        let r = vzip_u8(even.2, odd.2);
+        let out = dst.as_mut_ptr().add(block * 48);
+        vst3_u8(out, uint8x8x3_t(b.0, g.0, r.0));
+        vst3_u8(out.add(24), uint8x8x3_t(b.1, g.1, r.1));
+    }
+
+    scalar::yuyv_to_bgr(c, &src[blocks * 32..], &mut dst[blocks * 48..]);
+}
+
+#[target_feature(enable = "neon")]
+pub unsafe fn bgr_to_yuyv_neon(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 48;
+
+    for block in 0..blocks {
+        let first = vld3_u8(src.as_ptr().add(block * 48));
+        let second = vld3_u8(src.as_ptr().add(block * 48 + 24));
+        let (y1, u1, v1) = rgb_block(c, first.2, first.1, first.0);
+        let (y2, u2, v2) = rgb_block(c, second.2, second.1, second.0);
+
+        // split into even and odd pixels, chroma comes from the even ones
+        let luma = vuzp_u8(y1, y2);
+        let u = vuzp_u8(u1, u2).0;
+        let v = vuzp_u8(v1, v2).0;
+        vst4_u8(dst.as_mut_ptr().add(block * 32), uint8x8x4_t(luma.0, u, luma.1, v));
+    }
+
+    scalar::bgr_to_yuyv(c, &src[blocks * 48..], &mut dst[blocks * 32..]);
+}
@@ -0,0 +1,54 @@
+use super::{YuvCoefficients, ROUND, SHIFT};
+
+#[inline]
+fn clamp(val: i32) -> u8 {
+    val.clamp(0, 255) as u8
+}
+
+// The reference conversion of one pixel, every SIMD path must match it exactly
+#[inline]
+pub fn yuv_to_rgb(c: &YuvCoefficients, (y, u, v): (u8, u8, u8)) -> (u8, u8, u8) {
+    let yd = c.y_gain as i32 * (y as i32 - c.y_offset as i32);
+    let ud = u as i32 - 128;
+    let vd = v as i32 - 128;
+
+    let r = (yd + c.rv as i32 * vd + ROUND) >> SHIFT;
+    let g = (yd + c.gu as i32 * ud + c.gv as i32 * vd + ROUND) >> SHIFT;
+    let b = (yd + c.bu as i32 * ud + ROUND) >> SHIFT;
+
+    (clamp(r), clamp(g), clamp(b))
+}
+
+#[inline]
+pub fn rgb_to_yuv(c: &YuvCoefficients, (r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+
+    let y = ((c.yr as i32 * r + c.yg as i32 * g + c.yb as i32 * b + ROUND) >> SHIFT) + c.y_offset as i32;
+    let u = ((c.ur as i32 * r + c.ug as i32 * g + c.ub as i32 * b + ROUND) >> SHIFT) + 128;
+    let v = ((c.vr as i32 * r + c.vg as i32 * g + c.vb as i32 * b + ROUND) >> SHIFT) + 128;
+
+    (clamp(y), clamp(u), clamp(v))
+}
+
+pub fn yuyv_to_bgr(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    for (chunk, out) in src.chunks_exact(4).zip(dst.chunks_exact_mut(6)) {
+        let (y1, cb, y2, cr) = (chunk[0], chunk[1], chunk[2], chunk[3]);
+
+        let (r1, g1, b1) = yuv_to_rgb(c, (y1, cb, cr));
+        let (r2, g2, b2) = yuv_to_rgb(c, (y2, cb, cr));
+
+        out.copy_from_slice(&[b1, g1, r1, b2, g2, r2]);
+    }
+}
+
+pub fn bgr_to_yuyv(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    for (chunk, out) in src.chunks_exact(6).zip(dst.chunks_exact_mut(4)) {
+        let (r1, g1, b1) = (chunk[2], chunk[1], chunk[0]);
+        let (r2, g2, b2) = (chunk[5], chunk[4], chunk[3]);
+
+        let (y1, cb, cr) = rgb_to_yuv(c, (r1, g1, b1));
+        let (y2, _, _) = rgb_to_yuv(c, (r2, g2, b2));
+
+        out.copy_from_slice(&[y1, cb, y2, cr]);
+    }
+}
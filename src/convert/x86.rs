@@ -0,0 +1,251 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::{scalar, YuvCoefficients, ROUND, SHIFT};
+
+// Two i16 coefficients in one 32-bit lane, the layout _mm_madd_epi16 expects
+fn pair(lo: i16, hi: i16) -> i32 {
+    (((hi as u16 as u32) << 16) | lo as u16 as u32) as i32
+}
+
+/*
+ * SSE2: 8 pixels per iteration
+ */
+
+// lo/hi lanes of (a0 * coef0 + b0 * coef1, ...), each product exact in i32
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn madd_sse2(a: __m128i, b: __m128i, coef: __m128i) -> (__m128i, __m128i) {
+    (
+        _mm_madd_epi16(_mm_unpacklo_epi16(a, b), coef),
+        _mm_madd_epi16(_mm_unpackhi_epi16(a, b), coef),
+    )
+}
+
+// (sum + ROUND) >> SHIFT, plus offset, saturated to 8 u8 in the low half
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn narrow_sse2((lo, hi): (__m128i, __m128i), offset: __m128i) -> __m128i {
+    let round = _mm_set1_epi32(ROUND);
+    let lo = _mm_add_epi32(_mm_srai_epi32(_mm_add_epi32(lo, round), SHIFT), offset);
+    let hi = _mm_add_epi32(_mm_srai_epi32(_mm_add_epi32(hi, round), SHIFT), offset);
+    let words = _mm_packs_epi32(lo, hi);
+    _mm_packus_epi16(words, words)
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn add_sse2((a_lo, a_hi): (__m128i, __m128i), (b_lo, b_hi): (__m128i, __m128i)) -> (__m128i, __m128i) {
+    (_mm_add_epi32(a_lo, b_lo), _mm_add_epi32(a_hi, b_hi))
+}
+
+#[target_feature(enable = "sse2")]
+pub unsafe fn yuyv_to_bgr_sse2(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 16;
+
+    let low_bytes = _mm_set1_epi16(0x00FF);
+    let low_words = _mm_set1_epi32(0x0000FFFF);
+    let y_offset = _mm_set1_epi16(c.y_offset);
+    let chroma_offset = _mm_set1_epi16(128);
+    let zero = _mm_setzero_si128();
+    let coef_r = _mm_set1_epi32(pair(c.y_gain, c.rv));
+    let coef_g = _mm_set1_epi32(pair(c.y_gain, c.gu));
+    let coef_gv = _mm_set1_epi32(pair(c.gv, 0));
+    let coef_b = _mm_set1_epi32(pair(c.y_gain, c.bu));
+
+    let mut planes = [[0u8; 16]; 3];
+    for block in 0..blocks {
+        let pixels = _mm_loadu_si128(src.as_ptr().add(block * 16) as *const __m128i);
+
+        // Y0 U0 Y1 V0 ... -> one 16-bit lane per pixel
+        let luma = _mm_sub_epi16(_mm_and_si128(pixels, low_bytes), y_offset);
+        let chroma = _mm_srli_epi16(pixels, 8);
+        let u = _mm_and_si128(chroma, low_words);
+        let v = _mm_srli_epi32(chroma, 16);
+        let u = _mm_sub_epi16(_mm_or_si128(u, _mm_slli_epi32(u, 16)), chroma_offset);
+        let v = _mm_sub_epi16(_mm_or_si128(v, _mm_slli_epi32(v, 16)), chroma_offset);
+
+        let r = narrow_sse2(madd_sse2(luma, v, coef_r), zero);
+        let g = narrow_sse2(add_sse2(madd_sse2(luma, u, coef_g), madd_sse2(v, zero, coef_gv)), zero);
+        let b = narrow_sse2(madd_sse2(luma, u, coef_b), zero);
+
+        _mm_storeu_si128(planes[0].as_mut_ptr() as *mut __m128i, b);
+        _mm_storeu_si128(planes[1].as_mut_ptr() as *mut __m128i, g);
+        _mm_storeu_si128(planes[2].as_mut_ptr() as *mut __m128i, r);
+        interleave_bgr(&planes, 8, &mut dst[block * 24..block * 24 + 24]);
+    }
+
+    scalar::yuyv_to_bgr(c, &src[blocks * 16..], &mut dst[blocks * 24..]);
+}
+
+#[target_feature(enable = "sse2")]
+pub unsafe fn bgr_to_yuyv_sse2(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 24;
+
+    let y_offset = _mm_set1_epi32(c.y_offset as i32);
+    let chroma_offset = _mm_set1_epi32(128);
+    let zero = _mm_setzero_si128();
+    let coef_y = (_mm_set1_epi32(pair(c.yr, c.yg)), _mm_set1_epi32(pair(c.yb, 0)));
+    let coef_u = (_mm_set1_epi32(pair(c.ur, c.ug)), _mm_set1_epi32(pair(c.ub, 0)));
+    let coef_v = (_mm_set1_epi32(pair(c.vr, c.vg)), _mm_set1_epi32(pair(c.vb, 0)));
+
+    let mut channels = [[0i16; 8]; 3];
+    let mut planes = [[0u8; 16]; 3];
+    for block in 0..blocks {
+        deinterleave_bgr(&src[block * 24..block * 24 + 24], &mut channels);
+        let r = _mm_loadu_si128(channels[0].as_ptr() as *const __m128i);
+        let g = _mm_loadu_si128(channels[1].as_ptr() as *const __m128i);
+        let b = _mm_loadu_si128(channels[2].as_ptr() as *const __m128i);
+
+        let y = narrow_sse2(add_sse2(madd_sse2(r, g, coef_y.0), madd_sse2(b, zero, coef_y.1)), y_offset);
+        let u = narrow_sse2(add_sse2(madd_sse2(r, g, coef_u.0), madd_sse2(b, zero, coef_u.1)), chroma_offset);
+        let v = narrow_sse2(add_sse2(madd_sse2(r, g, coef_v.0), madd_sse2(b, zero, coef_v.1)), chroma_offset);
+
+        _mm_storeu_si128(planes[0].as_mut_ptr() as *mut __m128i, y);
+        _mm_storeu_si128(planes[1].as_mut_ptr() as *mut __m128i, u);
+        _mm_storeu_si128(planes[2].as_mut_ptr() as *mut __m128i, v);
+        interleave_yuyv(&planes, 8, &mut dst[block * 16..block * 16 + 16]);
+    }
+
+    scalar::bgr_to_yuyv(c, &src[blocks * 24..], &mut dst[blocks * 16..]);
+}
+
+/*
+ * AVX2: 16 pixels per iteration. Unpack and pack work inside each 128-bit
+ * lane, and the two cancel out, so pixel order is kept until the final
+ * cross-lane permute.
+ */
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn madd_avx2(a: __m256i, b: __m256i, coef: __m256i) -> (__m256i, __m256i) {
+    (
+        _mm256_madd_epi16(_mm256_unpacklo_epi16(a, b), coef),
+        _mm256_madd_epi16(_mm256_unpackhi_epi16(a, b), coef),
+    )
+}
+
+// (sum + ROUND) >> SHIFT, plus offset, saturated to 16 u8
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn narrow_avx2((lo, hi): (__m256i, __m256i), offset: __m256i) -> __m128i {
+    let round = _mm256_set1_epi32(ROUND);
+    let lo = _mm256_add_epi32(_mm256_srai_epi32(_mm256_add_epi32(lo, round), SHIFT), offset);
+    let hi = _mm256_add_epi32(_mm256_srai_epi32(_mm256_add_epi32(hi, round), SHIFT), offset);
+    let words = _mm256_packs_epi32(lo, hi);
+    let bytes = _mm256_packus_epi16(words, words);
+    // pixels 0-7 sit in quadword 0 and pixels 8-15 in quadword 2
+    _mm256_castsi256_si128(_mm256_permute4x64_epi64(bytes, 0b00_00_10_00))
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn add_avx2((a_lo, a_hi): (__m256i, __m256i), (b_lo, b_hi): (__m256i, __m256i)) -> (__m256i, __m256i) {
+    (_mm256_add_epi32(a_lo, b_lo), _mm256_add_epi32(a_hi, b_hi))
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn yuyv_to_bgr_avx2(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 32;
+
+    let low_bytes = _mm256_set1_epi16(0x00FF);
+    let low_words = _mm256_set1_epi32(0x0000FFFF);
+    let y_offset = _mm256_set1_epi16(c.y_offset);
+    let chroma_offset = _mm256_set1_epi16(128);
+    let zero = _mm256_setzero_si256();
+    let coef_r = _mm256_set1_epi32(pair(c.y_gain, c.rv));
+    let coef_g = _mm256_set1_epi32(pair(c.y_gain, c.gu));
+    let coef_gv = _mm256_set1_epi32(pair(c.gv, 0));
+    let coef_b = _mm256_set1_epi32(pair(c.y_gain, c.bu));
+
+    let mut planes = [[0u8; 16]; 3];
+    for block in 0..blocks {
+        let pixels = _mm256_loadu_si256(src.as_ptr().add(block * 32) as *const __m256i);
+
+        let luma = _mm256_sub_epi16(_mm256_and_si256(pixels, low_bytes), y_offset);
+        let chroma = _mm256_srli_epi16(pixels, 8);
+        let u = _mm256_and_si256(chroma, low_words);
+        let v = _mm256_srli_epi32(chroma, 16);
+        let u = _mm256_sub_epi16(_mm256_or_si256(u, _mm256_slli_epi32(u, 16)), chroma_offset);
+        let v = _mm256_sub_epi16(_mm256_or_si256(v, _mm256_slli_epi32(v, 16)), chroma_offset);
+
+        let r = narrow_avx2(madd_avx2(luma, v, coef_r), zero);
+        let g = narrow_avx2(add_avx2(madd_avx2(luma, u, coef_g), madd_avx2(v, zero, coef_gv)), zero);
+        let b = narrow_avx2(madd_avx2(luma, u, coef_b), zero);
+
+        _mm_storeu_si128(planes[0].as_mut_ptr() as *mut __m128i, b);
+        _mm_storeu_si128(planes[1].as_mut_ptr() as *mut __m128i, g);
+        _mm_storeu_si128(planes[2].as_mut_ptr() as *mut __m128i, r);
+        interleave_bgr(&planes, 16, &mut dst[block * 48..block * 48 + 48]);
+    }
+
+    scalar::yuyv_to_bgr(c, &src[blocks * 32..], &mut dst[blocks * 48..]);
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn bgr_to_yuyv_avx2(c: &YuvCoefficients, src: &[u8], dst: &mut [u8]) {
+    let blocks = src.len() / 48;
+
+    let y_offset = _mm256_set1_epi32(c.y_offset as i32);
+    let chroma_offset = _mm256_set1_epi32(128);
+    let zero = _mm256_setzero_si256();
+    let coef_y = (_mm256_set1_epi32(pair(c.yr, c.yg)), _mm256_set1_epi32(pair(c.yb, 0)));
+    let coef_u = (_mm256_set1_epi32(pair(c.ur, c.ug)), _mm256_set1_epi32(pair(c.ub, 0)));
+    let coef_v = (_mm256_set1_epi32(pair(c.vr, c.vg)), _mm256_set1_epi32(pair(c.vb, 0)));
+
+    let mut channels = [[0i16; 16]; 3];
+    let mut planes = [[0u8; 16]; 3];
+    for block in 0..blocks {
+        deinterleave_bgr(&src[block * 48..block * 48 + 48], &mut channels);
+        let r = _mm256_loadu_si256(channels[0].as_ptr() as *const __m256i);
+        let g = _mm256_loadu_si256(channels[1].as_ptr() as *const __m256i);
+        let b = _mm256_loadu_si256(channels[2].as_ptr() as *const __m256i);
+
+        let y = narrow_avx2(add_avx2(madd_avx2(r, g, coef_y.0), madd_avx2(b, zero, coef_y.1)), y_offset);
+        let u = narrow_avx2(add_avx2(madd_avx2(r, g, coef_u.0), madd_avx2(b, zero, coef_u.1)), chroma_offset);
+        let v = narrow_avx2(add_avx2(madd_avx2(r, g, coef_v.0), madd_avx2(b, zero, coef_v.1)), chroma_offset);
+
+        _mm_storeu_si128(planes[0].as_mut_ptr() as *mut __m128i, y);
+        _mm_storeu_si128(planes[1].as_mut_ptr() as *mut __m128i, u);
+        _mm_storeu_si128(planes[2].as_mut_ptr() as *mut __m128i, v);
+        interleave_yuyv(&planes, 16, &mut dst[block * 32..block * 32 + 32]);
+    }
+
+    scalar::bgr_to_yuyv(c, &src[blocks * 48..], &mut dst[blocks * 32..]);
+}
+
+/*
+ * Shuffles between planar lanes and packed pixels, x86 has no cheap
+ * 3-channel (de)interleave so these stay scalar
+ */
+
+#[inline]
+fn interleave_bgr(planes: &[[u8; 16]; 3], count: usize, out: &mut [u8]) {
+    for i in 0..count {
+        out[i * 3] = planes[0][i];
+        out[i * 3 + 1] = planes[1][i];
+        out[i * 3 + 2] = planes[2][i];
+    }
+}
+
+#[inline]
+fn interleave_yuyv(planes: &[[u8; 16]; 3], count: usize, out: &mut [u8]) {
+    for k in 0..count / 2 {
+        out[k * 4] = planes[0][2 * k];
+        out[k * 4 + 1] = planes[1][2 * k];
+        out[k * 4 + 2] = planes[0][2 * k + 1];
+        out[k * 4 + 3] = planes[2][2 * k];
+    }
+}
+
+// BGR pixels -> (r, g, b) planes of 16-bit lanes
+#[inline]
+fn deinterleave_bgr<const N: usize>(src: &[u8], channels: &mut [[i16; N]; 3]) {
+    for i in 0..N {
+        channels[0][i] = src[i * 3 + 2] as i16;
+        channels[1][i] = src[i * 3 + 1] as i16;
+        channels[2][i] = src[i * 3] as i16;
+    }
+}
@@ -0,0 +1,228 @@
+use crate::convert::scalar;
+use crate::keypoints;
+use crate::model::NUM_KEYPOINTS;
+use crate::types::{Image, PixelFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+
+    pub const GREEN: Color = Color::new(0, 255, 0);
+    pub const CYAN: Color = Color::new(0, 255, 255);
+    pub const WHITE: Color = Color::new(255, 255, 255);
+}
+
+// A color already laid out in the image's pixel format
+enum Ink {
+    Rgb([u8; 3]),
+    Grey(u8),
+    // luma, then U and V for the whole macropixel; `layout` as in resize_packed_422
+    Packed422 { luma: u8, u: u8, v: u8, layout: [usize; 4] }
+}
+
+fn ink(img: &Image, color: Color) -> Ink {
+    let yuv = || scalar::rgb_to_yuv(img.colorimetry.coefficients(), (color.r, color.g, color.b));
+    match img.pixel_format {
+        PixelFormat::RGB => Ink::Rgb([color.r, color.g, color.b]),
+        PixelFormat::BGR => Ink::Rgb([color.b, color.g, color.r]),
+        PixelFormat::GREY => Ink::Grey(yuv().0),
+        PixelFormat::YUYV | PixelFormat::UYVY => {
+            let (luma, u, v) = yuv();
+            let layout = match img.pixel_format {
+                PixelFormat::UYVY => [1, 0, 3, 2],
+                _ => [0, 1, 2, 3],
+            };
+            Ink::Packed422 { luma, u, v, layout }
+        }
+        other => panic!("drawing needs an RGB, BGR, GREY, YUYV or UYVY image, not {:?}", other),
+    }
+}
+
+// Sets one pixel, silently clipping anything outside the image
+fn put(img: &mut Image, ink: &Ink, x: i32, y: i32) {
+    if x < 0 || y < 0 || x >= img.width || y >= img.height {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    let row = y * img.stride;
+    match *ink {
+        Ink::Rgb(bytes) => img.data[row + x * 3..row + x * 3 + 3].copy_from_slice(&bytes),
+        Ink::Grey(luma) => img.data[row + x] = luma,
+        // the chroma is shared with the neighbouring pixel, which is close enough for overlays
+        Ink::Packed422 { luma, u, v, layout } => {
+            let macropixel = row + (x & !1) * 2;
+            img.data[macropixel + layout[(x & 1) * 2]] = luma;
+            img.data[macropixel + layout[1]] = u;
+            img.data[macropixel + layout[3]] = v;
+        }
+    }
+}
+
+fn disc(img: &mut Image, ink: &Ink, (cx, cy): (i32, i32), radius: i32) {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy <= radius * radius {
+                put(img, ink, cx + dx, cy + dy);
+            }
+        }
+    }
+}
+
+// Filled circle, a radius of 0 sets a single pixel
+pub fn draw_point(img: &mut Image, center: (i32, i32), radius: i32, color: Color) {
+    let ink = ink(img, color);
+    disc(img, &ink, center, radius);
+}
+
+// Bresenham line, thicker lines stamp a disc at every step
+pub fn draw_line(img: &mut Image, (x0, y0): (i32, i32), (x1, y1): (i32, i32), thickness: i32, color: Color) {
+    let ink = ink(img, color);
+    let radius = (thickness - 1).max(0) / 2;
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+    loop {
+        disc(img, &ink, (x, y), radius);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+pub const GLYPH_WIDTH: i32 = 5;
+pub const GLYPH_HEIGHT: i32 = 7;
+
+// 5x7 glyphs, one byte per row with the leftmost pixel in bit 4. Lowercase
+// letters are drawn as uppercase and anything else missing as '?'
+const FONT: [(char, [u8; 7]); 48] = [
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+];
+
+fn glyph(c: char) -> &'static [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .find(|(glyph_char, _)| *glyph_char == c)
+        .or_else(|| FONT.iter().find(|(glyph_char, _)| *glyph_char == '?'))
+        .map(|(_, rows)| rows)
+        .unwrap()
+}
+
+/** Draws one line of text with its top left corner at (x, y). Every font pixel
+** becomes a scale x scale block, and glyphs are one font pixel apart.
+**/
+pub fn draw_text(img: &mut Image, (x, y): (i32, i32), text: &str, scale: i32, color: Color) {
+    let ink = ink(img, color);
+    for (index, c) in text.chars().enumerate() {
+        let left = x + index as i32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put(img, &ink, left + column * scale + dx, y + row as i32 * scale + dy);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// MoveNet skeleton: face, arms, torso and legs
+const EDGES: [(usize, usize); 18] = [
+    (0, 1), (0, 2), (1, 3), (2, 4),
+    (5, 7), (7, 9), (6, 8), (8, 10),
+    (5, 6), (5, 11), (6, 12), (11, 12),
+    (11, 13), (13, 15), (12, 14), (14, 16),
+    (0, 5), (0, 6),
+];
+
+/** Draws the skeleton and joints of one MoveNet result, the Image counterpart of
+** utils::draw_keypoints. Joints below `threshold` and the bones touching them
+** are left out.
+**/
+pub fn draw_pose(img: &mut Image, keypoints: &[f32], threshold: f32) {
+    let size = (img.width, img.height);
+    let joint = |index: usize| -> Option<(i32, i32)> {
+        let keypoint = &keypoints[index * 3..index * 3 + 3];
+        (keypoint[2] > threshold).then(|| keypoints::to_pixel((keypoint[0], keypoint[1]), size))
+    };
+    // about 2 pixels at 640x480
+    let thickness = (img.width.max(img.height) / 320).max(1);
+
+    for (from, to) in EDGES {
+        if let (Some(from), Some(to)) = (joint(from), joint(to)) {
+            draw_line(img, from, to, thickness, Color::CYAN);
+        }
+    }
+    for index in 0..NUM_KEYPOINTS {
+        if let Some(center) = joint(index) {
+            draw_point(img, center, thickness * 2 + 1, Color::GREEN);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::types::{Image, PixelFormat};
+
+// Packed pixels ready for a file: GREY stays one channel, everything else becomes RGB
+fn file_pixels(img: &Image) -> Result<(Cow<'_, [u8]>, bool), String> {
+    Ok(match img.pixel_format {
+        PixelFormat::GREY if img.is_packed() => (Cow::Borrowed(&img.data[..(img.width * img.height) as usize]), true),
+        PixelFormat::GREY => {
+            let width = img.width as usize;
+            let rows = (0..img.height as usize).flat_map(|y| &img.data[y * img.stride..y * img.stride + width]);
+            (Cow::Owned(rows.copied().collect()), true)
+        }
+        PixelFormat::RGB if img.is_packed() => (Cow::Borrowed(&img.data[..(img.width * img.height * 3) as usize]), false),
+        _ => (Cow::Owned(img.to_rgb()?.data), false),
+    })
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("create {} [FAILED]: {}", path.display(), e))
+}
+
+// Binary PPM (P6), or PGM (P5) for grey images
+pub fn write_ppm(img: &Image, path: &Path) -> Result<(), String> {
+    let (pixels, grey) = file_pixels(img)?;
+    let mut file = create(path)?;
+    write!(file, "{}\n{} {}\n255\n", if grey { "P5" } else { "P6" }, img.width, img.height)
+        .and_then(|_| file.write_all(&pixels))
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("write PPM [FAILED]: {}", e))
+}
+
+pub fn write_png(img: &Image, path: &Path) -> Result<(), String> {
+    let (pixels, grey) = file_pixels(img)?;
+    let mut encoder = png::Encoder::new(create(path)?, img.width as u32, img.height as u32);
+    encoder.set_color(if grey { png::ColorType::Grayscale } else { png::ColorType::Rgb });
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("write PNG header [FAILED]: {}", e))?;
+    writer.write_image_data(&pixels).map_err(|e| format!("write PNG [FAILED]: {}", e))
+}
+
+// Baseline JPEG, quality from 1 to 100
+pub fn write_jpeg(img: &Image, path: &Path, quality: u8) -> Result<(), String> {
+    let (pixels, grey) = file_pixels(img)?;
+    let (width, height) = (u16::try_from(img.width), u16::try_from(img.height));
+    let (Ok(width), Ok(height)) = (width, height) else {
+        return Err(format!("{}x{} is too large for JPEG", img.width, img.height));
+    };
+    let color = if grey { jpeg_encoder::ColorType::Luma } else { jpeg_encoder::ColorType::Rgb };
+    jpeg_encoder::Encoder::new(create(path)?, quality)
+        .encode(&pixels, width, height, color)
+        .map_err(|e| format!("write JPEG [FAILED]: {}", e))
+}
+
+/** Writes an image of any pixel format, picking the encoder from the file
+** extension: .png, .ppm/.pgm or .jpg/.jpeg (at quality 90).
+**/
+pub fn save(img: &Image, path: &Path) -> Result<(), String> {
+    let extension = path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("png") => write_png(img, path),
+        Some("ppm") | Some("pgm") => write_ppm(img, path),
+        Some("jpg") | Some("jpeg") => write_jpeg(img, path, 90),
+        _ => Err(format!("unknown image extension in {}, expected png, ppm or jpg", path.display())),
+    }
+}
@@ -0,0 +1,29 @@
+use jpeg_decoder::{Decoder, PixelFormat as JpegPixelFormat};
+
+use crate::types::{Image, PixelFormat};
+
+/** Decodes one JPEG (or MJPEG camera frame, which may leave out its Huffman
+** tables) into an RGB or GREY image. With `min_size`, the decoder scales down
+** in the DCT domain by 1/2, 1/4 or 1/8, picking the smallest output that is
+** still at least min_size, which skips most of the IDCT work for large frames.
+**/
+pub fn decode(data: &[u8], min_size: Option<(usize, usize)>) -> Result<Image, String> {
+    let mut decoder = Decoder::new(data);
+    decoder.read_info().map_err(|e| format!("read JPEG header [FAILED]: {}", e))?;
+
+    if let Some((width, height)) = min_size {
+        let requested = (width.min(u16::MAX as usize) as u16, height.min(u16::MAX as usize) as u16);
+        decoder.scale(requested.0, requested.1)
+            .map_err(|e| format!("scale JPEG [FAILED]: {}", e))?;
+    }
+
+    let pixels = decoder.decode().map_err(|e| format!("decode JPEG [FAILED]: {}", e))?;
+    let info = decoder.info().ok_or("JPEG has no frame header")?;
+    let pixel_format = match info.pixel_format {
+        JpegPixelFormat::RGB24 => PixelFormat::RGB,
+        JpegPixelFormat::L8 => PixelFormat::GREY,
+        other => return Err(format!("unsupported JPEG pixel format {:?}", other)),
+    };
+
+    Ok(Image::new(pixels, info.width as i32, info.height as i32, pixel_format))
+}
@@ -0,0 +1,57 @@
+use crate::model::NUM_KEYPOINTS;
+use crate::types::{Mirror, Rotation};
+
+/** MoveNet returns [y, x, score] per keypoint, normalized to the padded square
+** the model saw. Both resize paths pad symmetrically to a square of the longer
+** side, and a quarter turn only swaps the sides, so the padded square of a
+** rotated frame is the rotated padded square of the original one. Undoing the
+** rotation is therefore a rotation of the unit square, independent of frame size.
+**/
+pub fn unrotate(keypoints: &mut [f32], rotation: Rotation) {
+    for keypoint in keypoints.chunks_exact_mut(3).take(NUM_KEYPOINTS) {
+        let (y, x) = (keypoint[0], keypoint[1]);
+        let (src_y, src_x) = match rotation {
+            Rotation::None => (y, x),
+            Rotation::Clockwise90 => (1.0 - x, y),
+            Rotation::Rotate180 => (1.0 - y, 1.0 - x),
+            Rotation::Clockwise270 => (x, 1.0 - y),
+        };
+        keypoint[0] = src_y;
+        keypoint[1] = src_x;
+    }
+}
+
+// Mirrors keypoints the same way Image::mirror mirrors the frame they belong to
+pub fn mirror(keypoints: &mut [f32], mirror: Mirror) {
+    for keypoint in keypoints.chunks_exact_mut(3).take(NUM_KEYPOINTS) {
+        if mirror.vertical() {
+            keypoint[0] = 1.0 - keypoint[0];
+        }
+        if mirror.horizontal() {
+            keypoint[1] = 1.0 - keypoint[1];
+        }
+    }
+}
+
+// MoveNet order: nose, then left/right pairs of eye, ear, shoulder, elbow, wrist, hip, knee, ankle
+const LEFT_RIGHT_PAIRS: [(usize, usize); 8] = [(1, 2), (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (13, 14), (15, 16)];
+
+/** Swaps every left joint with its right counterpart. After a horizontal mirror
+** the person's left arm shows up where a right arm would be; swapping the labels
+** too makes the skeleton read like the mirror image.
+**/
+pub fn swap_left_right(keypoints: &mut [f32]) {
+    for (left, right) in LEFT_RIGHT_PAIRS {
+        for i in 0..3 {
+            keypoints.swap(left * 3 + i, right * 3 + i);
+        }
+    }
+}
+
+// Pixel position of a normalized (y, x) keypoint in a width x height frame,
+// undoing the symmetric padding to a square of the longer side
+pub fn to_pixel((y_ratio, x_ratio): (f32, f32), (width, height): (i32, i32)) -> (i32, i32) {
+    let base = width.max(height);
+    let (pad_x, pad_y) = ((base - width) / 2, (base - height) / 2);
+    ((x_ratio * base as f32) as i32 - pad_x, (y_ratio * base as f32) as i32 - pad_y)
+}
@@ -3,9 +3,23 @@ use crate::types::Arguments;
 
 mod client;
 mod utils;
+#[cfg(feature = "bench")]
+mod bench;
 mod server;
 mod types;
+mod model;
+mod convert;
+mod jpeg;
+mod keypoints;
 mod proto;
+mod draw;
+mod encode;
+mod pool;
+mod record;
+mod source;
+#[cfg(feature = "opencv")]
+mod mat;
+#[cfg(feature = "opencv")]
 mod original;
 
 fn main() {
@@ -16,7 +30,37 @@ fn main() {
 	println!("{}", opt.server);
 	if opt.main {
 		println!("Starting main...");
+		#[cfg(feature = "opencv")]
 		original::main();
+		#[cfg(not(feature = "opencv"))]
+		eprintln!("Built without the opencv feature, -m is not available");
+	}
+	if opt.list_cameras {
+		match client::list_cameras() {
+			Ok(cameras) if cameras.is_empty() => println!("No capture cameras found"),
+			Ok(cameras) => cameras.iter().for_each(|camera| println!("{}", camera)),
+			Err(e) => eprintln!("List cameras error: {}", e),
+		}
+		return;
+	}
+	if opt.bench_convert {
+		if let Err(e) = convert::run_benchmark() {
+			eprintln!("Conversion benchmark error: {}", e);
+		}
+		return;
+	}
+	if opt.bench_preprocess {
+		#[cfg(feature = "bench")]
+		bench::run_preprocess_benchmark();
+		#[cfg(not(feature = "bench"))]
+		eprintln!("Built without the bench feature, --bench-preprocess is not available");
+		return;
+	}
+	if let Some(input) = &opt.annotate {
+		if let Err(e) = server::annotate_file(input, &opt.output, opt.model.as_deref(), opt.resample) {
+			eprintln!("Annotate error: {}", e);
+		}
+		return;
 	}
 	if opt.server {
 		println!("Starting server...");
@@ -25,9 +69,12 @@ fn main() {
 		}
 	} else if opt.client {
 		println!("Starting client...");
+		#[cfg(feature = "opencv")]
 		if let Err(e) = client::run_client() {
 			eprintln!("Client error: {}", e);
 		}
+		#[cfg(not(feature = "opencv"))]
+		eprintln!("Built without the opencv feature, -c is not available");
 	} else {
 		eprintln!("Invalid argument. Use -h for more info");
 	}
@@ -0,0 +1,160 @@
+/*
+ * OpenCV interop, only built with the `opencv` feature. Everything else works on
+ * Image buffers directly, so headless builds never link OpenCV.
+ */
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use opencv::core::{Mat, MatTraitConst, MatTraitConstManual, MatTraitManual, Scalar, Vec3b, CV_8UC1, CV_8UC2, CV_8UC3};
+use opencv::imgproc::{
+    cvt_color, COLOR_BGR5652BGR, COLOR_GRAY2BGR, COLOR_RGB2BGR, COLOR_YUV2BGR_I420,
+    COLOR_YUV2BGR_NV12, COLOR_YUV2BGR_UYVY, COLOR_YUV2BGR_YUYV,
+};
+
+use crate::convert::Colorimetry;
+use crate::types::{monotonic_micros, DisplayConverter, Image, ImageBuffer, PixelFormat};
+use crate::utils::convert_to_rgb24;
+
+impl<'a> ImageBuffer<'a> {
+    /** Borrows the pixels of a continuous 8-bit BGR or grey Mat without copying.
+    ** ROI Mats are not continuous, copy those with Image::from_mat instead.
+    **/
+    pub fn from_mat(image: &'a Mat) -> Result<ImageBuffer<'a>, String> {
+        let (pixel_format, channels) = match image.typ() {
+            CV_8UC3 => (PixelFormat::BGR, 3),
+            CV_8UC1 => (PixelFormat::GREY, 1),
+            other => return Err(format!("unsupported Mat type {}, expected CV_8UC3 or CV_8UC1", other)),
+        };
+        if !image.is_continuous() {
+            return Err("Mat is not continuous".to_string());
+        }
+        let buffer = image.data_bytes().map_err(|e| format!("get Mat data [FAILED]: {}", e))?;
+        let timestamp = monotonic_micros();
+
+        Ok(ImageBuffer {
+            timestamp,
+            buffer,
+            width: image.cols(),
+            height: image.rows(),
+            stride: image.cols() as usize * channels,
+            pixel_format,
+            colorimetry: Colorimetry::default(),
+            length: buffer.len() as i32
+        })
+    }
+}
+
+/** A Mat over the pixels of an Image instead of a copy of them. OpenCV does not
+** own foreign memory, so the borrow keeps the Image alive and unchanged for as
+** long as the view exists. Only &Mat is handed out, nothing writes through it.
+**/
+pub struct MatView<'a> {
+    mat: Mat,
+    _image: PhantomData<&'a [u8]>
+}
+
+impl<'a> Deref for MatView<'a> {
+    type Target = Mat;
+
+    fn deref(&self) -> &Mat {
+        &self.mat
+    }
+}
+
+impl Image {
+    pub fn from_mat(image: &Mat) -> Image {
+        if let Ok(buffer) = ImageBuffer::from_mat(image) {
+            return Image::from_image_buffer(&buffer);
+        }
+
+        let timestamp = monotonic_micros();
+
+        // ROI Mats are not continuous, so copy them row by row
+        let row_bytes = image.cols() as usize * 3;
+        let mut data = Vec::with_capacity(row_bytes * image.rows() as usize);
+        for row in 0..image.rows() {
+            let pixels = image.at_row::<Vec3b>(row).unwrap();
+            data.extend(pixels.iter().flat_map(|pixel| pixel.0));
+        }
+
+        Image {
+            timestamp,
+            sequence: 0,
+            data,
+            width: image.cols() as i32,
+            height: image.rows() as i32,
+            stride: row_bytes,
+            pixel_format: PixelFormat::BGR,
+            colorimetry: Colorimetry::default()
+        }
+    }
+
+    /** Wraps the pixels in a Mat without copying them, using the image stride as
+    ** the Mat step. Packed 4:2:2 and RGB565 become 2-channel Mats, NV12 and YU12
+    ** the single-channel (height * 3 / 2) x width layout cvtColor expects.
+    **/
+    pub fn as_mat(&self) -> Result<MatView<'_>, String> {
+        let (rows, typ) = match self.pixel_format {
+            PixelFormat::RGB | PixelFormat::BGR => (self.height, CV_8UC3),
+            PixelFormat::YUYV | PixelFormat::UYVY | PixelFormat::RGB565 => (self.height, CV_8UC2),
+            PixelFormat::GREY => (self.height, CV_8UC1),
+            PixelFormat::NV12 => (self.height + self.height / 2, CV_8UC1),
+            // the chroma planes only line up with the Mat rows when nothing is padded
+            PixelFormat::YU12 if self.is_packed() => (self.height + self.height / 2, CV_8UC1),
+            PixelFormat::YU12 => return Err("YU12 with a padded stride has no Mat layout".to_string()),
+            PixelFormat::MJPEG => return Err("compressed frames have no Mat layout".to_string()),
+        };
+        let needed = self.stride * (rows as usize - 1) + self.pixel_format.packed_stride(self.width as usize);
+        if self.data.len() < needed {
+            return Err(format!("{} bytes is too small for a {}x{} {:?} Mat", self.data.len(), self.width, rows, self.pixel_format));
+        }
+
+        // OpenCV takes a mutable pointer, but the view only ever reads through it
+        let mat = unsafe {
+            Mat::new_rows_cols_with_data(rows, self.width, typ, self.data.as_ptr() as *mut c_void, self.stride)
+        }.map_err(|e| format!("create Mat view [FAILED]: {}", e))?;
+
+        Ok(MatView { mat, _image: PhantomData })
+    }
+
+    pub fn to_mat(&self) -> Mat {
+        self.to_mat_with(DisplayConverter::Native)
+    }
+
+    // An owned BGR Mat, converted straight into the Mat memory. Compressed frames
+    // must be decoded first, with to_bgr
+    pub fn to_mat_with(&self, converter: DisplayConverter) -> Mat {
+        assert!(!self.pixel_format.is_compressed(), "{:?} frames must be decoded before display", self.pixel_format);
+        if self.pixel_format == PixelFormat::BGR {
+            return self.as_mat()
+                .and_then(|view| view.try_clone().map_err(|e| e.to_string()))
+                .expect("Copy BGR Mat [FAILED]");
+        }
+
+        if converter == DisplayConverter::OpenCv {
+            let code = match self.pixel_format {
+                PixelFormat::RGB => COLOR_RGB2BGR,
+                PixelFormat::GREY => COLOR_GRAY2BGR,
+                PixelFormat::YUYV => COLOR_YUV2BGR_YUYV,
+                PixelFormat::UYVY => COLOR_YUV2BGR_UYVY,
+                PixelFormat::NV12 => COLOR_YUV2BGR_NV12,
+                PixelFormat::YU12 => COLOR_YUV2BGR_I420,
+                // V4L2 RGB565 keeps blue in the low bits, which OpenCV calls BGR565
+                PixelFormat::RGB565 => COLOR_BGR5652BGR,
+                PixelFormat::BGR | PixelFormat::MJPEG => unreachable!(),
+            };
+            // padded YU12 has no view, our own converter handles it below
+            if let Ok(view) = self.as_mat() {
+                let mut mat = Mat::default();
+                cvt_color(&*view, &mut mat, code, 0).expect("cvtColor [FAILED]");
+                return mat;
+            }
+        }
+
+        let mut mat = Mat::new_rows_cols_with_default(self.height, self.width, CV_8UC3, Scalar::all(0.0))
+            .expect("Create Mat [FAILED]");
+        convert_to_rgb24(self, mat.data_bytes_mut().expect("Get Mat data [FAILED]"));
+        mat
+    }
+}
@@ -0,0 +1,153 @@
+use tflitec::interpreter::{Interpreter, Options};
+use tflitec::model::Model;
+use tflitec::tensor::{DataType, Tensor};
+
+use log::info;
+
+// The default model ships inside the binary so it runs from any working directory
+static DEFAULT_MODEL: &[u8] = include_bytes!("../resource/lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite");
+pub const DEFAULT_MODEL_NAME: &str = "embedded lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite";
+
+// Number of keypoints returned by the singlepose MoveNet models, each as (y, x, score)
+pub const NUM_KEYPOINTS: usize = 17;
+
+// The MoveNet variants we know how to feed and decode
+pub struct MoveNetSignature {
+    pub name: &'static str,
+    pub input_size: usize,
+}
+
+pub const SUPPORTED_MODELS: [MoveNetSignature; 2] = [
+    MoveNetSignature { name: "MoveNet SinglePose Lightning", input_size: 192 },
+    MoveNetSignature { name: "MoveNet SinglePose Thunder", input_size: 256 },
+];
+
+// What the server needs to know about a validated model
+#[derive(Clone, Copy)]
+pub struct ModelInfo {
+    pub name: &'static str,
+    pub input_width: usize,
+    pub input_height: usize,
+}
+
+// Reads the model at `path`, or the embedded default model when no path is given
+pub fn read_model(path: Option<&str>) -> Result<Vec<u8>, String> {
+    match path {
+        Some(path) => {
+            info!("loading model from {}", path);
+            std::fs::read(path).map_err(|e| format!("read model {} [FAILED]: {}", path, e))
+        }
+        None => {
+            info!("loading {}", DEFAULT_MODEL_NAME);
+            Ok(DEFAULT_MODEL.to_vec())
+        }
+    }
+}
+
+// The model borrows `bytes`, so every interpreter built from them sees the same model
+pub fn load_model(bytes: &[u8]) -> Result<Model, String> {
+    Model::from_bytes(bytes).map_err(|e| format!("load model [FAILED]: {:?}", e))
+}
+
+pub fn load_interpreter(model: &Model) -> Result<Interpreter, String> {
+    let options = Options::default();
+    let interpreter = Interpreter::new(model, Some(options))
+        .map_err(|e| format!("create interpreter [FAILED]: {:?}", e))?;
+    interpreter.allocate_tensors()
+        .map_err(|e| format!("allocate tensors [FAILED]: {:?}", e))?;
+    Ok(interpreter)
+}
+
+/** Checks the tensors of an interpreter against the known MoveNet signatures.
+** Prints a summary of every tensor, and returns an error describing the first
+** mismatch when the model is not one we can run.
+**/
+pub fn validate_model(interpreter: &Interpreter) -> Result<ModelInfo, String> {
+    let input_count = interpreter.input_tensor_count();
+    let output_count = interpreter.output_tensor_count();
+
+    println!("Model has {} input tensor(s) and {} output tensor(s)", input_count, output_count);
+    for index in 0..input_count {
+        let tensor = interpreter.input(index)
+            .map_err(|e| format!("read input tensor {} [FAILED]: {:?}", index, e))?;
+        println!("  input[{}]: {}", index, describe_tensor(&tensor));
+    }
+    for index in 0..output_count {
+        let tensor = interpreter.output(index)
+            .map_err(|e| format!("read output tensor {} [FAILED]: {:?}", index, e))?;
+        println!("  output[{}]: {}", index, describe_tensor(&tensor));
+    }
+
+    if input_count != 1 {
+        return Err(format!("expected 1 input tensor, model has {}", input_count));
+    }
+    if output_count != 1 {
+        return Err(format!("expected 1 output tensor, model has {}", output_count));
+    }
+
+    // input: [1, size, size, 3] uint8 pixels, fed without any rescaling
+    let input = interpreter.input(0)
+        .map_err(|e| format!("read input tensor [FAILED]: {:?}", e))?;
+    let input_dims = input.shape().dimensions().clone();
+    if input_dims.len() != 4 || input_dims[0] != 1 || input_dims[3] != 3 {
+        return Err(format!("expected input shape [1, H, W, 3], model has {:?}", input_dims));
+    }
+    if input_dims[1] != input_dims[2] {
+        return Err(format!("expected a square input, model has {}x{}", input_dims[2], input_dims[1]));
+    }
+    let signature = SUPPORTED_MODELS.iter()
+        .find(|signature| signature.input_size == input_dims[1])
+        .ok_or_else(|| format!(
+            "unsupported input size {}x{}, supported sizes are {}",
+            input_dims[2], input_dims[1],
+            SUPPORTED_MODELS.iter()
+                .map(|signature| format!("{} ({})", signature.input_size, signature.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+    if input.data_type() != DataType::Uint8 {
+        return Err(format!("expected uint8 input, model has {:?}", input.data_type()));
+    }
+    if let Some(params) = input.quantization_parameters() {
+        if params.scale != 1.0 || params.zero_point != 0 {
+            return Err(format!(
+                "expected raw pixel input (scale 1, zero point 0), model has scale {} and zero point {}",
+                params.scale, params.zero_point
+            ));
+        }
+    }
+
+    // output: [1, 1, 17, 3] float32 keypoints
+    let output = interpreter.output(0)
+        .map_err(|e| format!("read output tensor [FAILED]: {:?}", e))?;
+    let output_dims = output.shape().dimensions().clone();
+    if output_dims != vec![1, 1, NUM_KEYPOINTS, 3] {
+        return Err(format!("expected output shape [1, 1, {}, 3], model has {:?}", NUM_KEYPOINTS, output_dims));
+    }
+    if output.data_type() != DataType::Float32 {
+        return Err(format!("expected float32 output, model has {:?}", output.data_type()));
+    }
+
+    info!("model validated as {}", signature.name);
+    println!("Model recognized as {} ({}x{} input)", signature.name, signature.input_size, signature.input_size);
+
+    Ok(ModelInfo {
+        name: signature.name,
+        input_width: signature.input_size,
+        input_height: signature.input_size,
+    })
+}
+
+fn describe_tensor(tensor: &Tensor) -> String {
+    let quantization = match tensor.quantization_parameters() {
+        Some(params) => format!("scale {}, zero point {}", params.scale, params.zero_point),
+        None => "not quantized".to_string(),
+    };
+    format!(
+        "'{}' {:?} {:?} ({})",
+        tensor.name(),
+        tensor.data_type(),
+        tensor.shape().dimensions(),
+        quantization
+    )
+}
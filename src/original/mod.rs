@@ -8,33 +8,56 @@ use opencv::{
 };
 use crate::utils::draw_keypoints;
 use crate::utils::resize_with_padding;
-use tflitec::interpreter::{Interpreter, Options};
-use tflitec::model::Model;
+use structopt::StructOpt;
+use crate::model::{load_interpreter, load_model, read_model, validate_model, ModelInfo, DEFAULT_MODEL_NAME};
+use crate::types::{Arguments, DisplayConverter, SourceSpec};
+use crate::client::open_source;
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, SourceError};
+use tflitec::interpreter::Interpreter;
 
 pub fn main() {
     // load model and create interpreter
-    let options = Options::default();
-    let path = format!("resource/lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite");
-    let model = Model::new(&path).expect("Load model [FAILED]");
-    let interpreter = Interpreter::new(&model, Some(options)).expect("Create interpreter [FAILED]");
-    interpreter.allocate_tensors().expect("Allocate tensors [FAILED]");
+    let opt = Arguments::from_args();
+    let model_bytes = read_model(opt.model.as_deref()).expect("Read model [FAILED]");
+    let model = load_model(&model_bytes).expect("Load model [FAILED]");
+    let interpreter = load_interpreter(&model).expect("Create interpreter [FAILED]");
+    let model_info = match validate_model(&interpreter) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("Unsupported model {}: {}", opt.model.as_deref().unwrap_or(DEFAULT_MODEL_NAME), e);
+            return;
+        }
+    };
     // Resize input
 
-    // open camera
-    let mut cam = videoio::VideoCapture::new(0, videoio::CAP_ANY).unwrap(); // 0 is the default camera
-    videoio::VideoCapture::is_opened(&cam).expect("Open camera [FAILED]");
-    cam.set(CAP_PROP_FPS, 30.0).expect("Set camera FPS [FAILED]");
+    // open camera, or whichever source --source names
+    let mut source = open_source(&opt, SourceSpec::OpenCv).expect("Open frame source [FAILED]");
+    println!("Using source {}", source.name());
+    run(&mut source, &interpreter, &model_info);
+}
 
+fn run<S: FrameSource>(source: &mut S, interpreter: &Interpreter, model_info: &ModelInfo) {
+    let pool = BufferPool::new(2);
     loop {
-        let mut frame = Mat::default();
-        cam.read(&mut frame).expect("VideoCapture: read [FAILED]");
+        let frame = match source.next_frame(&pool) {
+            Ok(Some(image)) => {
+                let frame = image.to_mat_with(DisplayConverter::Native);
+                pool.recycle(image);
+                frame
+            }
+            // no frame yet, leaves an empty Mat
+            Ok(None) => Mat::default(),
+            Err(SourceError::Ended) => break,
+            Err(e) => panic!("Read frame [FAILED]: {}", e),
+        };
 
         if frame.size().unwrap().width > 0 {
             // flip the image horizontally
             let mut flipped = Mat::default();
             flip(&frame, &mut flipped, 1).expect("flip [FAILED]");
-            // resize the image as a square, size is
-            let resized_img = resize_with_padding(&flipped, [192, 192]);
+            // resize the image as a square, size is given by the model
+            let resized_img = resize_with_padding(&flipped, [model_info.input_width as i32, model_info.input_height as i32]);
 
             // turn Mat into Vec<u8>
             let vec_2d: Vec<Vec<Vec3b>> = resized_img.to_vec_2d().unwrap();
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::types::{Image, PixelFormat};
+
+/** A free list of byte buffers, so a steady stream of same-sized frames stops
+** allocating after the first few. `take` hands out the smallest pooled buffer
+** that is large enough, a hit, or allocates a new one, a miss. Once every stage
+** returns what it takes, misses stop growing; `stats` is there to check that.
+** The pool is shared by reference, server threads may use one concurrently.
+**/
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub pooled: usize,
+}
+
+impl fmt::Display for PoolStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} hits, {} misses, {} buffers pooled", self.hits, self.misses, self.pooled)
+    }
+}
+
+impl BufferPool {
+    // Keeps at most `max_buffers` returned buffers, extra ones are freed
+    pub fn new(max_buffers: usize) -> BufferPool {
+        BufferPool {
+            free: Mutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // A buffer of exactly `len` bytes. Reused buffers keep their old contents,
+    // only bytes beyond their previous length are zeroed
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        let reused = {
+            let mut free = self.free.lock().expect("Lock buffer pool [FAILED]");
+            let best = free.iter()
+                .enumerate()
+                .filter(|(_, buffer)| buffer.capacity() >= len)
+                .min_by_key(|(_, buffer)| buffer.capacity())
+                .map(|(index, _)| index);
+            best.map(|index| free.swap_remove(index))
+        };
+
+        match reused {
+            Some(mut buffer) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                buffer.resize(len, 0);
+                buffer
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                vec![0; len]
+            }
+        }
+    }
+
+    pub fn give(&self, buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        let mut free = self.free.lock().expect("Lock buffer pool [FAILED]");
+        if free.len() < self.max_buffers {
+            free.push(buffer);
+        }
+    }
+
+    // A packed width x height image backed by a pooled buffer
+    pub fn take_image(&self, width: i32, height: i32, pixel_format: PixelFormat) -> Image {
+        let stride = pixel_format.packed_stride(width as usize);
+        let data = self.take(pixel_format.frame_size_with_stride(stride, height as usize));
+        Image::with_stride(data, width, height, stride, pixel_format)
+    }
+
+    // Hands the buffer of an image back once it is no longer needed
+    pub fn recycle(&self, image: Image) {
+        self.give(image.data);
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            pooled: self.free.lock().expect("Lock buffer pool [FAILED]").len(),
+        }
+    }
+}
+
+impl Image {
+    // to_bgr into a pooled buffer; compressed frames still allocate in the decoder
+    pub fn to_bgr_pooled(&self, pool: &BufferPool) -> Result<Image, String> {
+        if self.pixel_format.is_compressed() {
+            return self.to_bgr();
+        }
+        let mut bgr = pool.take_image(self.width, self.height, PixelFormat::BGR);
+        crate::utils::convert_to_rgb24(self, &mut bgr.data);
+        Ok(bgr.with_capture_info(self.timestamp, self.sequence))
+    }
+}
@@ -0,0 +1,342 @@
+/*
+ * Raw frame recordings: every frame exactly as the source delivered it, with
+ * its format, colorimetry, capture time and sequence number, so a session can
+ * be replayed bit for bit (source::ReplaySource).
+ *
+ * Layout, all integers little endian:
+ *   file header    "MNREC001"
+ *   frame records  "FRAM", timestamp u64, sequence u32, fourcc u32, width u32,
+ *                  height u32, stride u32, yuv matrix u16 (601 or 709),
+ *                  limited range u8, 0 u8, data length u32, data
+ *   index          "INDX", frame count u32, record offsets u64 each
+ *   trailer        index offset u64, "MNRIDX01"
+ * The index is written last. A recording cut short has none, the reader then
+ * walks the records from the start and keeps the complete ones.
+ */
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use log::warn;
+
+use crate::convert::{Colorimetry, YuvMatrix, YuvRange};
+use crate::pool::BufferPool;
+use crate::types::{Image, PixelFormat};
+
+const FILE_MAGIC: &[u8; 8] = b"MNREC001";
+const FRAME_MAGIC: &[u8; 4] = b"FRAM";
+const INDEX_MAGIC: &[u8; 4] = b"INDX";
+const TRAILER_MAGIC: &[u8; 8] = b"MNRIDX01";
+const FRAME_HEADER_LEN: usize = 4 + 8 + 4 * 5 + 2 + 1 + 1 + 4;
+const TRAILER_LEN: u64 = 16;
+
+pub struct Recorder {
+    file: BufWriter<File>,
+    offsets: Vec<u64>,
+    position: u64,
+    finished: bool,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("create {} [FAILED]: {}", path.display(), e))?;
+        let mut file = BufWriter::new(file);
+        file.write_all(FILE_MAGIC).map_err(|e| format!("write recording header [FAILED]: {}", e))?;
+        Ok(Recorder { file, offsets: Vec::new(), position: FILE_MAGIC.len() as u64, finished: false })
+    }
+
+    // Appends the frame as is, compressed frames stay compressed
+    pub fn write(&mut self, img: &Image) -> Result<(), String> {
+        let length = img.data.len();
+        let mut header = Vec::with_capacity(FRAME_HEADER_LEN);
+        header.extend_from_slice(FRAME_MAGIC);
+        header.extend_from_slice(&img.timestamp.to_le_bytes());
+        header.extend_from_slice(&img.sequence.to_le_bytes());
+        header.extend_from_slice(&img.pixel_format.fourcc().to_le_bytes());
+        header.extend_from_slice(&(img.width as u32).to_le_bytes());
+        header.extend_from_slice(&(img.height as u32).to_le_bytes());
+        header.extend_from_slice(&(img.stride as u32).to_le_bytes());
+        let matrix: u16 = match img.colorimetry.matrix {
+            YuvMatrix::Bt601 => 601,
+            YuvMatrix::Bt709 => 709,
+        };
+        header.extend_from_slice(&matrix.to_le_bytes());
+        header.push((img.colorimetry.range == YuvRange::Limited) as u8);
+        header.push(0);
+        header.extend_from_slice(&(length as u32).to_le_bytes());
+
+        self.file.write_all(&header)
+            .and_then(|_| self.file.write_all(&img.data))
+            .map_err(|e| format!("write frame {} [FAILED]: {}", img.sequence, e))?;
+        self.offsets.push(self.position);
+        self.position += (header.len() + length) as u64;
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    // Writes the index and flushes, recordings that are never finished stay readable
+    pub fn finish(mut self) -> Result<(), String> {
+        self.write_index()
+    }
+
+    fn write_index(&mut self) -> Result<(), String> {
+        self.finished = true;
+        let mut index = Vec::with_capacity(8 + self.offsets.len() * 8 + TRAILER_LEN as usize);
+        index.extend_from_slice(INDEX_MAGIC);
+        index.extend_from_slice(&(self.offsets.len() as u32).to_le_bytes());
+        self.offsets.iter().for_each(|offset| index.extend_from_slice(&offset.to_le_bytes()));
+        index.extend_from_slice(&self.position.to_le_bytes());
+        index.extend_from_slice(TRAILER_MAGIC);
+        self.file.write_all(&index)
+            .and_then(|_| self.file.flush())
+            .map_err(|e| format!("write recording index [FAILED]: {}", e))
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.write_index() {
+                warn!("{}", e);
+            }
+        }
+    }
+}
+
+/** Reads a recording frame by frame, in the order it was written. Frames come
+** back with their recorded timestamp and sequence number.
+**/
+pub struct RecordingReader {
+    file: BufReader<File>,
+    offsets: Vec<u64>,
+    // bounds what a damaged record header can claim
+    file_length: u64,
+}
+
+fn read_array<const N: usize>(file: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    file.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+impl RecordingReader {
+    pub fn open(path: &Path) -> Result<RecordingReader, String> {
+        let file = File::open(path).map_err(|e| format!("open {} [FAILED]: {}", path.display(), e))?;
+        let mut file = BufReader::new(file);
+        match read_array::<8>(&mut file) {
+            Ok(magic) if magic == *FILE_MAGIC => {}
+            _ => return Err(format!("{} is not a recording", path.display())),
+        }
+
+        let offsets = match Self::read_index(&mut file) {
+            Ok(offsets) => offsets,
+            Err(e) => {
+                warn!("{}: {}, scanning the frames instead", path.display(), e);
+                Self::scan(&mut file)
+            }
+        };
+        let file_length = file.seek(SeekFrom::End(0)).map_err(|e| format!("read {} [FAILED]: {}", path.display(), e))?;
+        Ok(RecordingReader { file, offsets, file_length })
+    }
+
+    fn read_index(file: &mut BufReader<File>) -> Result<Vec<u64>, String> {
+        let invalid = |e: std::io::Error| format!("no index ({})", e);
+        let length = file.seek(SeekFrom::End(0)).map_err(invalid)?;
+        if length < FILE_MAGIC.len() as u64 + TRAILER_LEN {
+            return Err("no index".to_string());
+        }
+        file.seek(SeekFrom::End(-(TRAILER_LEN as i64))).map_err(invalid)?;
+        let index_offset = u64::from_le_bytes(read_array(file).map_err(invalid)?);
+        if read_array::<8>(file).map_err(invalid)? != *TRAILER_MAGIC || index_offset >= length {
+            return Err("no index".to_string());
+        }
+
+        file.seek(SeekFrom::Start(index_offset)).map_err(invalid)?;
+        if read_array::<4>(file).map_err(invalid)? != *INDEX_MAGIC {
+            return Err("damaged index".to_string());
+        }
+        let count = u32::from_le_bytes(read_array(file).map_err(invalid)?) as u64;
+        if index_offset + 8 + count * 8 + TRAILER_LEN != length {
+            return Err("damaged index".to_string());
+        }
+        (0..count)
+            .map(|_| read_array(file).map(u64::from_le_bytes).map_err(invalid))
+            .collect()
+    }
+
+    // Offsets of the complete records, for recordings without an index
+    fn scan(file: &mut BufReader<File>) -> Vec<u64> {
+        let mut offsets = Vec::new();
+        let mut position = FILE_MAGIC.len() as u64;
+        while let Ok(length) = Self::record_length(file, position) {
+            offsets.push(position);
+            position += length;
+        }
+        offsets
+    }
+
+    // Length of the record at `offset`, if it is complete
+    fn record_length(file: &mut BufReader<File>, offset: u64) -> std::io::Result<u64> {
+        file.seek(SeekFrom::Start(offset))?;
+        let header: [u8; FRAME_HEADER_LEN] = read_array(file)?;
+        if header[..4] != *FRAME_MAGIC {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        let data_length = u32::from_le_bytes(header[FRAME_HEADER_LEN - 4..].try_into().unwrap()) as u64;
+        let end = file.seek(SeekFrom::End(0))?;
+        if offset + FRAME_HEADER_LEN as u64 + data_length > end {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(FRAME_HEADER_LEN as u64 + data_length)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    // Frame `index`, in a buffer from `pool`
+    pub fn read_frame(&mut self, index: usize, pool: &BufferPool) -> Result<Image, String> {
+        let offset = *self.offsets.get(index).ok_or(format!("no frame {} in the recording", index))?;
+        let invalid = |e: std::io::Error| format!("read recorded frame {} [FAILED]: {}", index, e);
+        self.file.seek(SeekFrom::Start(offset)).map_err(invalid)?;
+        let header: [u8; FRAME_HEADER_LEN] = read_array(&mut self.file).map_err(invalid)?;
+        if header[..4] != *FRAME_MAGIC {
+            return Err(format!("recorded frame {} is damaged", index));
+        }
+
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let timestamp = u64::from_le_bytes(header[4..12].try_into().unwrap());
+        let (sequence, fourcc, width, height, stride) = (u32_at(12), u32_at(16), u32_at(20), u32_at(24), u32_at(28));
+        let matrix = match u16::from_le_bytes([header[32], header[33]]) {
+            709 => YuvMatrix::Bt709,
+            _ => YuvMatrix::Bt601,
+        };
+        let range = if header[34] != 0 { YuvRange::Limited } else { YuvRange::Full };
+        let length = u32_at(36) as usize;
+        let pixel_format = PixelFormat::from_fourcc(fourcc)
+            .ok_or(format!("recorded frame {} has unknown format {:#010x}", index, fourcc))?;
+
+        // the header is only as good as the file, check it before allocating or reading
+        if offset + (FRAME_HEADER_LEN + length) as u64 > self.file_length {
+            return Err(format!("recorded frame {} claims {} bytes, more than the recording holds", index, length));
+        }
+        let (width, height, stride) = (width as usize, height as usize, stride as usize);
+        if width > i32::MAX as usize || height > i32::MAX as usize || stride < pixel_format.packed_stride(width)
+            || (!pixel_format.is_compressed() && length < pixel_format.frame_size_with_stride(stride, height)) {
+            return Err(format!(
+                "recorded frame {} is damaged: {:?} {}x{} with stride {} in {} bytes",
+                index, pixel_format, width, height, stride, length
+            ));
+        }
+
+        let mut data = pool.take(length);
+        if let Err(e) = self.file.read_exact(&mut data) {
+            pool.give(data);
+            return Err(invalid(e));
+        }
+        Ok(Image::with_stride(data, width as i32, height as i32, stride, pixel_format)
+            .with_colorimetry(Colorimetry { matrix, range })
+            .with_capture_info(timestamp, sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    // A recording path of its own for every test, tests run in parallel
+    fn recording_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rust_movenet_{}_{}.mnrec", name, std::process::id()))
+    }
+
+    // Frames that differ in every field the header carries, one of them padded
+    fn frames() -> Vec<Image> {
+        let yuyv = Image::with_stride((0..=255).cycle().take(8 * 4 * 2).collect(), 8, 4, 8 * 2, PixelFormat::YUYV)
+            .with_colorimetry(Colorimetry { matrix: YuvMatrix::Bt709, range: YuvRange::Limited })
+            .with_capture_info(1_000_000, 7);
+        let padded_grey = Image::with_stride((0..=255).rev().cycle().take(12 * 3).collect(), 10, 3, 12, PixelFormat::GREY)
+            .with_capture_info(1_033_333, 9);
+        let mjpeg = Image::with_stride(vec![0xFF, 0xD8, 0xFF, 0xD9], 640, 480, 0, PixelFormat::MJPEG)
+            .with_capture_info(u64::MAX - 1, u32::MAX);
+        vec![yuyv, padded_grey, mjpeg]
+    }
+
+    fn assert_same(read: &Image, written: &Image) {
+        assert_eq!(read.data, written.data);
+        assert_eq!((read.width, read.height, read.stride), (written.width, written.height, written.stride));
+        assert_eq!(read.pixel_format, written.pixel_format);
+        assert_eq!(read.colorimetry, written.colorimetry);
+        assert_eq!((read.timestamp, read.sequence), (written.timestamp, written.sequence));
+    }
+
+    fn record(path: &Path, frames: &[Image]) {
+        let mut recorder = Recorder::create(path).unwrap();
+        frames.iter().for_each(|frame| recorder.write(frame).unwrap());
+        assert_eq!(recorder.frame_count(), frames.len());
+        recorder.finish().unwrap();
+    }
+
+    #[test]
+    fn indexed_recording_round_trips() {
+        let path = recording_path("indexed");
+        let frames = frames();
+        record(&path, &frames);
+
+        let pool = BufferPool::new(2);
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), frames.len());
+        // the index allows any order
+        for index in (0..frames.len()).rev() {
+            assert_same(&reader.read_frame(index, &pool).unwrap(), &frames[index]);
+        }
+        assert!(reader.read_frame(frames.len(), &pool).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_recording_keeps_complete_frames() {
+        let path = recording_path("truncated");
+        let frames = frames();
+        record(&path, &frames);
+
+        // cut off the index and the last byte of the last frame, as a crash would
+        let length = FILE_MAGIC.len() + frames.iter().map(|frame| FRAME_HEADER_LEN + frame.data.len()).sum::<usize>();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length as u64 - 1).unwrap();
+
+        let pool = BufferPool::new(2);
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), frames.len() - 1);
+        for (index, frame) in frames[..frames.len() - 1].iter().enumerate() {
+            assert_same(&reader.read_frame(index, &pool).unwrap(), frame);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn damaged_frame_headers_are_errors() {
+        let path = recording_path("damaged");
+        let frames = frames();
+        record(&path, &frames);
+        let header_at = |index: usize| FILE_MAGIC.len() + frames[..index].iter().map(|frame| FRAME_HEADER_LEN + frame.data.len()).sum::<usize>();
+        let damage = |at: usize, value: u32| {
+            let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+            file.seek(SeekFrom::Start(at as u64)).unwrap();
+            file.write_all(&value.to_le_bytes()).unwrap();
+        };
+
+        // a data length past the end of the file, then a stride shorter than a row
+        damage(header_at(0) + 36, u32::MAX);
+        damage(header_at(1) + 28, 2);
+
+        let pool = BufferPool::new(2);
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert!(reader.read_frame(0, &pool).is_err());
+        assert!(reader.read_frame(1, &pool).is_err());
+        assert_same(&reader.read_frame(2, &pool).unwrap(), &frames[2]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
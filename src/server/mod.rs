@@ -1,3 +1,3 @@
 mod server_main;
 
-pub use server_main::run_server;
\ No newline at end of file
+pub use server_main::{annotate_file, run_server};
\ No newline at end of file
@@ -1,22 +1,42 @@
 use std::fmt::Debug;
 use std::net::{TcpListener, TcpStream};
-use std::io::{Read, Write};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::sync::Arc;
 use std::thread;
 use prost::Message;
 use structopt::StructOpt;
 use crate::proto::DnnRequest;
 use crate::proto::DnnResponse;
 
-use tflitec::interpreter::{Interpreter, Options};
+use tflitec::interpreter::Interpreter;
 use tflitec::tensor::Tensor;
-use tflitec::model::Model;
-use crate::types::{InferenceResults, Arguments, Image, COLOR_SPACE};
+use crate::types::{InferenceResults, Arguments, FrameInfo, Image, PixelFormat, ResampleMode, Rotation};
+use crate::model::{load_interpreter, load_model, read_model, validate_model, ModelInfo, DEFAULT_MODEL_NAME, NUM_KEYPOINTS};
+use crate::jpeg;
+use crate::convert::{Colorimetry, YuvMatrix, YuvRange};
+use crate::keypoints;
+use crate::draw;
+use crate::encode;
+use crate::pool::BufferPool;
 
 use log::{info, warn};
-use crate::utils::{resize_with_padding_ultra_fast, rgb24_to_yuv422, yuv422_to_rgb24};
+use crate::utils::{preprocess_into, rotate_image};
 
 pub fn run_server() -> std::io::Result<()> {
     let opt = Arguments::from_args();
+
+    // refuse to start if the model cannot be served
+    let model_name = opt.model.clone().unwrap_or_else(|| DEFAULT_MODEL_NAME.to_string());
+    // every connection builds its interpreter from these bytes, not from the file again
+    let model_bytes = Arc::new(read_model(opt.model.as_deref())
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?);
+    let model = load_model(&model_bytes)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let interpreter = load_interpreter(&model)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let model_info = validate_model(&interpreter)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, format!("unsupported model {}: {}", model_name, e)))?;
+
     let listener = TcpListener::bind(opt.bind)?;
     println!("Server listening on port 10026");
 
@@ -24,7 +44,9 @@ pub fn run_server() -> std::io::Result<()> {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                thread::spawn(|| handle_client(stream));
+                let model_bytes = Arc::clone(&model_bytes);
+                let resample = opt.resample;
+                thread::spawn(move || handle_client(stream, &model_bytes, model_info, resample));
             }
             Err(e) => {
                 eprintln!("Failed to accept a connection: {}", e);
@@ -34,35 +56,104 @@ pub fn run_server() -> std::io::Result<()> {
     Ok(())
 }
 
-fn inference(interpreter : &Interpreter, yuv_input: Vec<u8>, (original_width, original_height): (u32, u32)) -> Vec<f32> {
+/** Runs one frame through the model. `input` is consumed and its buffer, like
+** every intermediate image, goes back to `pool` once the tensor is filled.
+** The size and stride of `frame` must already be checked against the input length.
+**/
+fn inference(interpreter : &Interpreter, model_info: &ModelInfo, pool: &BufferPool, resample: ResampleMode, input: Vec<u8>, frame: &FrameInfo) -> Vec<f32> {
 
-    assert!(yuv_input.len() %2 == 0, "YUV422 input size must be even");
-
-    let mut original_image: Image = Image::new(yuv_input, original_width as i32, original_height as i32, COLOR_SPACE::YUV);
-    let mut resized = resize_with_padding_ultra_fast(&original_image, (192, 192), COLOR_SPACE::YUV);
-    let mut resized_rgb = vec![0; resized.data.len() * 3/2];
+    let original_image: Image = if frame.pixel_format.is_compressed() {
+        // let the decoder downscale in the DCT domain to just above the model input size
+        let decoded = jpeg::decode(&input, Some((model_info.input_width, model_info.input_height)));
+        pool.give(input);
+        match decoded {
+            Ok(image) => image,
+            Err(e) => {
+                // webcams do send the odd truncated frame, report no keypoints for it
+                warn!("Dropping undecodable {:?} frame: {}", frame.pixel_format, e);
+                return vec![0.0; NUM_KEYPOINTS * 3];
+            }
+        }
+    } else {
+        Image::with_stride(input, frame.width as i32, frame.height as i32, frame.stride, frame.pixel_format)
+            .with_colorimetry(frame.colorimetry)
+    };
 
-    yuv422_to_rgb24(&resized.data[..], &mut resized_rgb);
-    interpreter.copy(&resized_rgb[..], 0).unwrap();
+    // turn sideways or upside down cameras upright before the model sees them
+    let original_image = match frame.rotation {
+        Rotation::None => original_image,
+        rotation => {
+            let rotated = rotate_image(&original_image, rotation, pool);
+            pool.recycle(original_image);
+            rotated
+        }
+    };
 
-    // interpreter.copy(&yuv_input[..], 0).unwrap();
+    // resize, pad and convert straight into the input tensor
+    let time_start = std::time::Instant::now();
+    {
+        let mut input_tensor = interpreter.input(0).expect("Get input tensor [FAILED]");
+        preprocess_into(
+            &original_image,
+            input_tensor.data_mut::<u8>(),
+            (model_info.input_width as i32, model_info.input_height as i32),
+            resample
+        );
+    }
+    info!("Preprocessing took: {:?}", time_start.elapsed());
+    pool.recycle(original_image);
 
     interpreter.invoke().expect("Invoke [FAILED]");
 
     let output_tensor = interpreter.output(0).unwrap();
-    output_tensor.data::<f32>().to_vec()
+    let mut keypoints = output_tensor.data::<f32>().to_vec();
+    keypoints::unrotate(&mut keypoints, frame.rotation);
+    keypoints
 
 
 }
 
-fn handle_client(mut stream: TcpStream) {
+/** Runs the model on one image file and writes it back out with the pose drawn
+** on top, for batch jobs that have no camera and no display. JPEG inputs are
+** decoded twice: downscaled for the model and at full size for the output.
+**/
+pub fn annotate_file(input_path: &str, output_path: &str, model_path: Option<&str>, resample: ResampleMode) -> Result<(), String> {
+    let model_bytes = read_model(model_path)?;
+    let model = load_model(&model_bytes)?;
+    let interpreter = load_interpreter(&model)?;
+    let model_info = validate_model(&interpreter)?;
+
+    let input = std::fs::read(input_path).map_err(|e| format!("read {} [FAILED]: {}", input_path, e))?;
+    let image = jpeg::decode(&input, None)?;
+    // `input` is the file itself, which inference decodes again downscaled
+    let frame = FrameInfo { pixel_format: PixelFormat::MJPEG, stride: 0, ..image.frame_info(Rotation::None) };
+    let keypoints = inference(&interpreter, &model_info, &BufferPool::new(2), resample, input, &frame);
+
+    let mut image = image.to_rgb()?;
+    draw::draw_pose(&mut image, &keypoints, 0.25);
+    encode::save(&image, std::path::Path::new(output_path))
+}
+
+// `model_bytes` and `model_info` come from run_server, which read and validated the model once
+fn handle_client(mut stream: TcpStream, model_bytes: &[u8], model_info: ModelInfo, resample: ResampleMode) {
     let mut buffer = vec![0; 1024];
 
-    let options = Options::default();
-    let path = "resource/lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite";
-    let model = Model::new(&path).expect("Load model [FAILED]");
-    let interpreter = Interpreter::new(&model, Some(options)).expect("Create interpreter [FAILED]");
-    interpreter.allocate_tensors().expect("Allocate tensors [FAILED]");
+    let model = match load_model(model_bytes) {
+        Ok(model) => model,
+        Err(e) => {
+            eprintln!("{}, closing connection", e);
+            return;
+        }
+    };
+    let interpreter = match load_interpreter(&model) {
+        Ok(interpreter) => interpreter,
+        Err(e) => {
+            eprintln!("{}, closing connection", e);
+            return;
+        }
+    };
+    // one received frame plus a rotated copy and a resize scratch buffer
+    let pool = BufferPool::new(4);
 
 
     loop {
@@ -85,25 +176,97 @@ fn handle_client(mut stream: TcpStream) {
         let message = DnnRequest::decode(&buffer[..message_length]).expect("Failed to decode message");
 
         // Read the image data based on the length
-        let mut image_vec = Vec::with_capacity(message.image_num_bytes as usize);
-        image_vec.resize(message.image_num_bytes as usize, 0);
+        let mut image_vec = pool.take(message.image_num_bytes as usize);
         stream.read_exact(&mut image_vec).expect("Failed to read full image");
-        info!("Received Image timestamp: {}", message.timestamp);
+        info!("Received frame {} captured at {} us", message.sequence, message.timestamp);
+
+        // older clients leave the format unset and always send YUYV
+        let pixel_format = match message.pixel_format {
+            0 => PixelFormat::YUYV,
+            code => match PixelFormat::from_fourcc(code) {
+                Some(format) => format,
+                None => {
+                    eprintln!("Unsupported pixel format {:#010x}, closing connection", code);
+                    return;
+                }
+            }
+        };
+        let matrix = match message.yuv_matrix {
+            0 | 601 => YuvMatrix::Bt601,
+            709 => YuvMatrix::Bt709,
+            other => {
+                eprintln!("Unsupported YUV matrix BT.{}, closing connection", other);
+                return;
+            }
+        };
+        let range = if message.limited_range { YuvRange::Limited } else { YuvRange::Full };
+        let rotation = match Rotation::from_degrees(message.rotation) {
+            Some(rotation) => rotation,
+            None => {
+                eprintln!("Unsupported rotation of {} degrees, closing connection", message.rotation);
+                return;
+            }
+        };
+        if message.width == 0 || message.height == 0 {
+            eprintln!("Empty {}x{} frame, closing connection", message.width, message.height);
+            return;
+        }
+        // a partial macropixel or chroma row would be read past its end
+        let (chroma_width, chroma_height) = pixel_format.subsampling();
+        if !(message.width as usize).is_multiple_of(chroma_width) || !(message.height as usize).is_multiple_of(chroma_height) {
+            eprintln!("{:?} cannot be {}x{}, closing connection", pixel_format, message.width, message.height);
+            return;
+        }
+        // older clients leave the stride unset and send packed rows
+        let packed_stride = pixel_format.packed_stride(message.width as usize);
+        let stride = match message.stride {
+            0 => packed_stride,
+            stride => stride as usize,
+        };
+        if stride < packed_stride {
+            eprintln!("{:?} stride {} is too short for width {}, closing connection", pixel_format, stride, message.width);
+            return;
+        }
+        // compressed frames vary in length, the decoder checks those
+        let frame_size = if pixel_format.is_compressed() {
+            0
+        } else {
+            pixel_format.frame_size_with_stride(stride, message.height as usize)
+        };
+        if (message.image_num_bytes as usize) < frame_size {
+            eprintln!(
+                "{:?} frame of {} bytes is too small for {}x{} with stride {}, closing connection",
+                pixel_format, message.image_num_bytes, message.width, message.height, stride
+            );
+            return;
+        }
+        let frame = FrameInfo {
+            width: message.width,
+            height: message.height,
+            stride,
+            pixel_format,
+            colorimetry: Colorimetry { matrix, range },
+            rotation,
+            timestamp: message.timestamp,
+            sequence: message.sequence,
+        };
         let response = DnnResponse {
             timestamp: message.timestamp,
-            vector: inference(&interpreter, image_vec, (message.width, message.height)),
+            sequence: message.sequence,
+            vector: inference(&interpreter, &model_info, &pool, resample, image_vec, &frame),
         };
 
         // handle encoding of the response and sending it back
         let mut response_buffer = Vec::new();
         response.encode(&mut response_buffer).expect("Failed to encode response");
         let response_length = response_buffer.len() as u32;
-        info!("Image {}: Sending response of length: {}", message.timestamp, response_length);
+        info!("Frame {}: Sending response of length: {}", message.sequence, response_length);
         stream.write_all(&response_length.to_be_bytes()).expect("Failed to write response length");
         stream.write_all(&response_buffer).expect("Failed to write response");
 
         let time_end = std::time::Instant::now();
         let elapsed = time_end - time_start;
-        println!("Image {}: Inference took: {:?}", message.timestamp, elapsed);
+        println!("Frame {}: Inference took: {:?}", message.sequence, elapsed);
+        info!("Frame {}: buffer pool {}", message.sequence, pool.stats());
     }
 }
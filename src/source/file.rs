@@ -0,0 +1,160 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::jpeg;
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, Pacer, SourceError};
+use crate::types::{monotonic_micros, Image, PixelFormat};
+
+const EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "ppm", "pgm"];
+
+fn extension_of(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+}
+
+fn read_png(path: &Path) -> Result<Image, String> {
+    let file = File::open(path).map_err(|e| format!("open {} [FAILED]: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // palettes and 16 bit samples come out as 8 bit RGB or grey
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("read PNG header [FAILED]: {}", e))?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| format!("read PNG [FAILED]: {}", e))?;
+    data.truncate(info.buffer_size());
+
+    let (width, height) = (info.width as i32, info.height as i32);
+    match info.color_type {
+        png::ColorType::Rgb => Ok(Image::new(data, width, height, PixelFormat::RGB)),
+        png::ColorType::Grayscale => Ok(Image::new(data, width, height, PixelFormat::GREY)),
+        // drop alpha, a camera frame has none
+        png::ColorType::Rgba => {
+            let rgb = data.chunks_exact(4).flat_map(|pixel| &pixel[..3]).copied().collect();
+            Ok(Image::new(rgb, width, height, PixelFormat::RGB))
+        }
+        png::ColorType::GrayscaleAlpha => {
+            let grey = data.chunks_exact(2).map(|pixel| pixel[0]).collect();
+            Ok(Image::new(grey, width, height, PixelFormat::GREY))
+        }
+        other => Err(format!("unsupported PNG color type {:?}", other)),
+    }
+}
+
+// Binary PPM (P6) or PGM (P5) with 8 bit samples, as write_ppm makes them
+fn read_ppm(path: &Path) -> Result<Image, String> {
+    let bytes = fs::read(path).map_err(|e| format!("read {} [FAILED]: {}", path.display(), e))?;
+
+    // magic, width, height and maxval, separated by whitespace and # comments
+    let mut fields = Vec::new();
+    let mut position = 0;
+    while fields.len() < 4 && position < bytes.len() {
+        match bytes[position] {
+            b'#' => {
+                while position < bytes.len() && bytes[position] != b'\n' {
+                    position += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => position += 1,
+            _ => {
+                let start = position;
+                while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+                    position += 1;
+                }
+                fields.push(String::from_utf8_lossy(&bytes[start..position]).into_owned());
+            }
+        }
+    }
+    // exactly one whitespace byte separates the header from the pixels
+    let pixels = bytes.get(position + 1..).unwrap_or_default();
+
+    let [magic, width, height, maxval] = &fields[..] else {
+        return Err(format!("{} has no complete PPM header", path.display()));
+    };
+    let pixel_format = match magic.as_str() {
+        "P6" => PixelFormat::RGB,
+        "P5" => PixelFormat::GREY,
+        other => return Err(format!("unsupported PPM type {}, expected P5 or P6", other)),
+    };
+    let parse = |field: &str| field.parse::<i32>().map_err(|e| format!("bad PPM header field '{}': {}", field, e));
+    let (width, height) = (parse(width)?, parse(height)?);
+    if maxval != "255" {
+        return Err(format!("unsupported PPM maxval {}, expected 255", maxval));
+    }
+
+    let size = pixel_format.frame_size(width as usize, height as usize);
+    if pixels.len() < size {
+        return Err(format!("{} is truncated, {} of {} pixel bytes", path.display(), pixels.len(), size));
+    }
+    Ok(Image::new(pixels[..size].to_vec(), width, height, pixel_format))
+}
+
+// Decodes an image file by its extension: JPEG, PNG or PPM/PGM
+pub fn read_image(path: &Path) -> Result<Image, String> {
+    match extension_of(path).as_deref() {
+        Some("jpg") | Some("jpeg") => {
+            let bytes = fs::read(path).map_err(|e| format!("read {} [FAILED]: {}", path.display(), e))?;
+            jpeg::decode(&bytes, None)
+        }
+        Some("png") => read_png(path),
+        Some("ppm") | Some("pgm") => read_ppm(path),
+        _ => Err(format!("unknown image extension in {}, expected jpg, png or ppm", path.display())),
+    }
+}
+
+/** Plays back an image file, or every image in a directory in file name order,
+** as if a camera had captured them. Video files need the OpenCV source.
+**/
+pub struct FileSource {
+    path: PathBuf,
+    files: Vec<PathBuf>,
+    next: usize,
+    repeat: bool,
+    sequence: u32,
+    pacer: Pacer,
+}
+
+impl FileSource {
+    // `fps` 0 reads frames as fast as they are asked for, `repeat` starts over after the last one
+    pub fn new(path: &Path, fps: u32, repeat: bool) -> Result<FileSource, String> {
+        let files = if path.is_dir() {
+            let entries = fs::read_dir(path).map_err(|e| format!("read {} [FAILED]: {}", path.display(), e))?;
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|file| extension_of(file).is_some_and(|extension| EXTENSIONS.contains(&extension.as_str())))
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        if files.is_empty() {
+            return Err(format!("{} has no jpg, png or ppm images", path.display()));
+        }
+        Ok(FileSource { path: path.to_path_buf(), files, next: 0, repeat, sequence: 0, pacer: Pacer::new(fps) })
+    }
+}
+
+impl FrameSource for FileSource {
+    fn next_frame(&mut self, _pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        if self.next == self.files.len() {
+            if !self.repeat {
+                return Err(SourceError::Ended);
+            }
+            self.next = 0;
+        }
+        self.pacer.wait();
+
+        let image = read_image(&self.files[self.next])?;
+        self.next += 1;
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(Some(image.with_capture_info(monotonic_micros(), sequence)))
+    }
+
+    fn name(&self) -> String {
+        format!("{} ({} images)", self.path.display(), self.files.len())
+    }
+}
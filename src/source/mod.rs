@@ -0,0 +1,127 @@
+/*
+ * Frame sources: anything that yields a stream of Images with their format,
+ * capture time and sequence number. The client and the local mode only see
+ * this trait, so they run the same with a V4L2 camera (client::camera), an
+ * OpenCV capture, a folder of images, a recording or a generated test pattern.
+ */
+mod file;
+mod replay;
+mod synthetic;
+#[cfg(feature = "opencv")]
+mod opencv;
+
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::pool::BufferPool;
+use crate::types::Image;
+
+pub use file::FileSource;
+pub use replay::ReplaySource;
+pub use synthetic::SyntheticSource;
+#[cfg(feature = "opencv")]
+pub use self::opencv::OpenCvSource;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceError {
+    // a file or recording has no frames left
+    Ended,
+    Failed(String),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceError::Ended => write!(f, "no frames left"),
+            SourceError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+// Whether a source is delivering, for sources whose device can go away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceStatus {
+    Live,
+    // waiting for the device to come back
+    Disconnected,
+}
+
+impl From<String> for SourceError {
+    fn from(e: String) -> SourceError {
+        SourceError::Failed(e)
+    }
+}
+
+pub trait FrameSource {
+    /** The next frame, in a buffer taken from `pool` where the source can, so the
+    ** caller should recycle it once done. Ok(None) means no frame is ready yet,
+    ** a camera that timed out for instance, and the caller may simply try again.
+    **/
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError>;
+
+    // What the frames come from, for the log
+    fn name(&self) -> String;
+
+    // Frames the source captured but never delivered
+    fn dropped_frames(&self) -> u64 {
+        0
+    }
+
+    fn status(&self) -> SourceStatus {
+        SourceStatus::Live
+    }
+}
+
+impl<S: FrameSource + ?Sized> FrameSource for Box<S> {
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        (**self).next_frame(pool)
+    }
+
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn dropped_frames(&self) -> u64 {
+        (**self).dropped_frames()
+    }
+
+    fn status(&self) -> SourceStatus {
+        (**self).status()
+    }
+}
+
+// Spaces out frames of sources that could deliver them as fast as they are read
+pub(crate) struct Pacer {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl Pacer {
+    // 0 fps means no pacing at all
+    pub(crate) fn new(fps: u32) -> Pacer {
+        let interval = match fps {
+            0 => None,
+            fps => Some(Duration::from_secs(1) / fps),
+        };
+        Pacer { interval, next: None }
+    }
+
+    // Sleeps until the next frame is due. A late frame does not make the
+    // following ones come faster, the schedule restarts from it
+    pub(crate) fn wait(&mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let now = Instant::now();
+        match self.next {
+            Some(next) if next > now => {
+                thread::sleep(next - now);
+                self.next = Some(next + interval);
+            }
+            _ => self.next = Some(now + interval),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use opencv::core::Mat;
+use opencv::prelude::*;
+use opencv::videoio::{self, VideoCapture, CAP_ANY, CAP_PROP_FPS};
+
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, SourceError};
+use crate::types::{monotonic_micros, Image};
+
+/** Frames from an OpenCV VideoCapture: a camera index, or a video file or
+** stream URL that OpenCV can decode. They arrive as BGR copies of the Mats.
+**/
+pub struct OpenCvSource {
+    capture: VideoCapture,
+    name: String,
+    // a file ends, a camera just has no frame for now
+    is_file: bool,
+    sequence: u32,
+}
+
+impl OpenCvSource {
+    pub fn open(target: &str, fps: u32) -> Result<OpenCvSource, String> {
+        let (capture, is_file) = match target.parse::<i32>() {
+            Ok(index) => (VideoCapture::new(index, CAP_ANY), false),
+            Err(_) => (VideoCapture::from_file(target, CAP_ANY), true),
+        };
+        let mut capture = capture.map_err(|e| format!("open {} [FAILED]: {}", target, e))?;
+        if !videoio::VideoCapture::is_opened(&capture).unwrap_or(false) {
+            return Err(format!("open {} [FAILED]", target));
+        }
+        if !is_file {
+            capture.set(CAP_PROP_FPS, fps as f64).map_err(|e| format!("set camera FPS [FAILED]: {}", e))?;
+        }
+        Ok(OpenCvSource { capture, name: format!("opencv {}", target), is_file, sequence: 0 })
+    }
+}
+
+impl FrameSource for OpenCvSource {
+    fn next_frame(&mut self, _pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        let mut frame = Mat::default();
+        let read = self.capture.read(&mut frame).map_err(|e| format!("VideoCapture: read [FAILED]: {}", e))?;
+        if !read || frame.cols() == 0 {
+            return if self.is_file { Err(SourceError::Ended) } else { Ok(None) };
+        }
+
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(Some(Image::from_mat(&frame).with_capture_info(monotonic_micros(), sequence)))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::pool::BufferPool;
+use crate::record::RecordingReader;
+use crate::source::{FrameSource, SourceError};
+use crate::types::{monotonic_micros, Image, ReplayTiming};
+
+/** Plays back a recording made with --record. The bytes, formats and sequence
+** numbers are the recorded ones, so gaps from dropped frames show up again.
+** Timestamps keep their recorded spacing but are moved to the current clock,
+** latencies measured against them stay meaningful.
+**/
+pub struct ReplaySource {
+    reader: RecordingReader,
+    name: String,
+    timing: ReplayTiming,
+    repeat: bool,
+    next: usize,
+    // when replay started, recorded timestamp of its first frame, and the same on our clock
+    start: Option<(Instant, u64, u64)>,
+}
+
+impl ReplaySource {
+    pub fn open(path: &Path, timing: ReplayTiming, repeat: bool) -> Result<ReplaySource, String> {
+        let reader = RecordingReader::open(path)?;
+        if reader.frame_count() == 0 {
+            return Err(format!("{} has no frames", path.display()));
+        }
+        let name = format!("replay of {} ({} frames)", path.display(), reader.frame_count());
+        Ok(ReplaySource { reader, name, timing, repeat, next: 0, start: None })
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        if self.next == self.reader.frame_count() {
+            if !self.repeat {
+                return Err(SourceError::Ended);
+            }
+            self.next = 0;
+            self.start = None;
+        }
+
+        let image = self.reader.read_frame(self.next, pool)?;
+        self.next += 1;
+
+        let (started, first_recorded, first_replayed) = *self.start
+            .get_or_insert_with(|| (Instant::now(), image.timestamp, monotonic_micros()));
+        let offset = image.timestamp.saturating_sub(first_recorded);
+        if self.timing == ReplayTiming::Original {
+            let due = started + Duration::from_micros(offset);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        let timestamp = match self.timing {
+            ReplayTiming::Original => first_replayed + offset,
+            ReplayTiming::Fast => monotonic_micros(),
+        };
+        let sequence = image.sequence;
+        Ok(Some(image.with_capture_info(timestamp, sequence)))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::convert::Colorimetry;
+use crate::pool::BufferPool;
+use crate::source::{FrameSource, Pacer, SourceError};
+use crate::types::{monotonic_micros, Image, PixelFormat};
+use crate::utils::{rgb24_to_yuv422, rgb_to_ycbcr};
+
+// BGR colour bars: white, yellow, cyan, green, magenta, red, blue, black
+const BARS: [[u8; 3]; 8] = [
+    [255, 255, 255], [0, 255, 255], [255, 255, 0], [0, 255, 0],
+    [255, 0, 255], [0, 0, 255], [255, 0, 0], [0, 0, 0],
+];
+
+/** Colour bars with a grey square sliding across them, one step per frame, so
+** every frame differs and a stuck pipeline shows. Frames come in any of the
+** uncompressed formats a camera would send that we can generate exactly:
+** RGB, BGR, GREY, YUYV and UYVY.
+**/
+pub struct SyntheticSource {
+    width: i32,
+    height: i32,
+    pixel_format: PixelFormat,
+    colorimetry: Colorimetry,
+    sequence: u32,
+    pacer: Pacer,
+}
+
+impl SyntheticSource {
+    // `fps` 0 generates frames as fast as they are asked for
+    pub fn new(width: u32, height: u32, pixel_format: PixelFormat, fps: u32) -> Result<SyntheticSource, String> {
+        match pixel_format {
+            PixelFormat::RGB | PixelFormat::BGR | PixelFormat::GREY => {}
+            PixelFormat::YUYV | PixelFormat::UYVY if width.is_multiple_of(2) => {}
+            PixelFormat::YUYV | PixelFormat::UYVY => return Err(format!("{:?} needs an even width, not {}", pixel_format, width)),
+            other => return Err(format!("cannot generate {:?} frames, use RGB, BGR, GREY, YUYV or UYVY", other)),
+        }
+        if width == 0 || height == 0 {
+            return Err(format!("cannot generate {}x{} frames", width, height));
+        }
+        Ok(SyntheticSource {
+            width: width as i32,
+            height: height as i32,
+            pixel_format,
+            colorimetry: Colorimetry::default(),
+            sequence: 0,
+            pacer: Pacer::new(fps),
+        })
+    }
+
+    fn draw_bgr(&self, bgr: &mut [u8]) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let side = (width.min(height) / 4).max(1);
+        let travel = (width - side.min(width)).max(1);
+        let left = self.sequence as usize * 4 % travel;
+        let top = (height - side.min(height)) / 2;
+
+        for (y, row) in bgr.chunks_exact_mut(width * 3).enumerate() {
+            for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                let in_square = (left..left + side).contains(&x) && (top..top + side).contains(&y);
+                pixel.copy_from_slice(if in_square { &[128, 128, 128] } else { &BARS[x * BARS.len() / width] });
+            }
+        }
+    }
+}
+
+impl FrameSource for SyntheticSource {
+    fn next_frame(&mut self, pool: &BufferPool) -> Result<Option<Image>, SourceError> {
+        self.pacer.wait();
+        let timestamp = monotonic_micros();
+
+        let mut bgr = pool.take_image(self.width, self.height, PixelFormat::BGR);
+        self.draw_bgr(&mut bgr.data);
+        let coefficients = self.colorimetry.coefficients();
+        let image = match self.pixel_format {
+            PixelFormat::BGR => bgr,
+            PixelFormat::RGB => {
+                bgr.data.chunks_exact_mut(3).for_each(|pixel| pixel.swap(0, 2));
+                bgr.pixel_format = PixelFormat::RGB;
+                bgr
+            }
+            PixelFormat::GREY => {
+                let mut grey = pool.take_image(self.width, self.height, PixelFormat::GREY);
+                for (luma, pixel) in grey.data.iter_mut().zip(bgr.data.chunks_exact(3)) {
+                    *luma = rgb_to_ycbcr(coefficients, (pixel[2], pixel[1], pixel[0])).0;
+                }
+                pool.recycle(bgr);
+                grey
+            }
+            // YUYV or UYVY, new checked the format
+            pixel_format => {
+                let mut yuv = pool.take_image(self.width, self.height, pixel_format);
+                rgb24_to_yuv422(coefficients, &bgr.data, &mut yuv.data);
+                if pixel_format == PixelFormat::UYVY {
+                    yuv.data.chunks_exact_mut(2).for_each(|pair| pair.swap(0, 1));
+                }
+                pool.recycle(bgr);
+                yuv.with_colorimetry(self.colorimetry)
+            }
+        };
+
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(Some(image.with_capture_info(timestamp, sequence)))
+    }
+
+    fn name(&self) -> String {
+        format!("synthetic {}x{} {:?}", self.width, self.height, self.pixel_format)
+    }
+}
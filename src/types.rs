@@ -1,27 +1,341 @@
 use std::cmp::PartialEq;
-use std::ffi::c_void;
-use std::time::{SystemTime, UNIX_EPOCH};
-use opencv::core::{Mat, MatTraitConst, MatTraitConstManual, Vec3b};
+use std::str::FromStr;
 use structopt::StructOpt;
 use tflitec::tensor::Tensor;
-use crate::types::COLOR_SPACE::RGB;
-use crate::utils::yuv422_to_rgb24;
+use crate::utils::convert_to_rgb24;
+use crate::jpeg;
+use crate::convert::Colorimetry;
 
 pub struct InferenceResults {
     pub(crate) timestamp: u64,
+    pub(crate) sequence: u32,
     pub(crate) vector: Vec<f32>
 }
 
-pub enum COLOR_SPACE {
-    RGB,
-    YUV
+/** Microseconds on CLOCK_MONOTONIC, the clock V4L2 stamps captured buffers
+** with. Frames that do not come from a camera are stamped with it too, so the
+** time from capture to results can be measured the same way for every frame.
+**/
+pub fn monotonic_micros() -> u64 {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000
 }
+
+// v4l2_fourcc(a, b, c, d) from videodev2.h
+pub const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+}
+
+// Memory layouts a frame can arrive in, named after their V4L2 fourcc
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelFormat {
+    RGB,    // packed R G B
+    BGR,    // packed B G R, the channel order OpenCV uses
+    YUYV,   // packed 4:2:2, Y0 U Y1 V
+    UYVY,   // packed 4:2:2, U Y0 V Y1
+    NV12,   // 4:2:0, Y plane followed by an interleaved UV plane
+    YU12,   // 4:2:0, Y plane followed by U and V planes (V4L2_PIX_FMT_YUV420)
+    GREY,   // 8-bit luma only
+    RGB565, // packed little-endian 5:6:5
+    MJPEG,  // one JPEG image per frame, variable length
+}
+
+impl PixelFormat {
+    pub fn fourcc(&self) -> u32 {
+        match self {
+            PixelFormat::RGB => fourcc(b"RGB3"),
+            PixelFormat::BGR => fourcc(b"BGR3"),
+            PixelFormat::YUYV => fourcc(b"YUYV"),
+            PixelFormat::UYVY => fourcc(b"UYVY"),
+            PixelFormat::NV12 => fourcc(b"NV12"),
+            PixelFormat::YU12 => fourcc(b"YU12"),
+            PixelFormat::GREY => fourcc(b"GREY"),
+            PixelFormat::RGB565 => fourcc(b"RGBP"),
+            PixelFormat::MJPEG => fourcc(b"MJPG"),
+        }
+    }
+
+    pub fn from_fourcc(code: u32) -> Option<PixelFormat> {
+        [
+            PixelFormat::RGB,
+            PixelFormat::BGR,
+            PixelFormat::YUYV,
+            PixelFormat::UYVY,
+            PixelFormat::NV12,
+            PixelFormat::YU12,
+            PixelFormat::GREY,
+            PixelFormat::RGB565,
+            PixelFormat::MJPEG,
+        ].into_iter().find(|format| format.fourcc() == code)
+    }
+
+    // Compressed frames have to be decoded before any pixel can be read
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, PixelFormat::MJPEG)
+    }
+
+    // Bytes per row of a tightly packed frame (of the luma plane for planar formats)
+    pub fn packed_stride(&self, width: usize) -> usize {
+        match self {
+            PixelFormat::RGB | PixelFormat::BGR => width * 3,
+            PixelFormat::YUYV | PixelFormat::UYVY | PixelFormat::RGB565 => width * 2,
+            PixelFormat::NV12 | PixelFormat::YU12 | PixelFormat::GREY => width,
+            PixelFormat::MJPEG => 0,
+        }
+    }
+
+    // How many pixels share one chroma sample (horizontally, vertically); frame sizes are multiples of it
+    pub fn subsampling(&self) -> (usize, usize) {
+        match self {
+            PixelFormat::YUYV | PixelFormat::UYVY => (2, 1),
+            PixelFormat::NV12 | PixelFormat::YU12 => (2, 2),
+            _ => (1, 1),
+        }
+    }
+
+    // Size in bytes of a tightly packed width x height frame, 0 when it varies per frame
+    pub fn frame_size(&self, width: usize, height: usize) -> usize {
+        self.frame_size_with_stride(self.packed_stride(width), height)
+    }
+
+    /** Size in bytes of a frame whose rows are `stride` bytes apart. As in V4L2,
+    ** the stride is that of the luma plane, NV12 chroma rows share it and YU12
+    ** chroma rows use half of it.
+    **/
+    pub fn frame_size_with_stride(&self, stride: usize, height: usize) -> usize {
+        match self {
+            PixelFormat::NV12 => stride * height + stride * (height / 2),
+            PixelFormat::YU12 => stride * height + 2 * (stride / 2) * (height / 2),
+            _ => stride * height,
+        }
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "RGB" | "RGB3" => Ok(PixelFormat::RGB),
+            "BGR" | "BGR3" => Ok(PixelFormat::BGR),
+            "YUYV" => Ok(PixelFormat::YUYV),
+            "UYVY" => Ok(PixelFormat::UYVY),
+            "NV12" => Ok(PixelFormat::NV12),
+            "YU12" | "YUV420" | "I420" => Ok(PixelFormat::YU12),
+            "GREY" | "GRAY" => Ok(PixelFormat::GREY),
+            "RGB565" | "RGBP" => Ok(PixelFormat::RGB565),
+            "MJPEG" | "MJPG" => Ok(PixelFormat::MJPEG),
+            _ => Err(format!("unknown pixel format '{}', expected rgb, bgr, yuyv, uyvy, nv12, yu12, grey, rgb565 or mjpeg", s)),
+        }
+    }
+}
+
+// How resize functions sample the source image, from fastest to most accurate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResampleMode {
+    Nearest,
+    Bilinear,
+    Area
+}
+
+impl FromStr for ResampleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" => Ok(ResampleMode::Nearest),
+            "bilinear" => Ok(ResampleMode::Bilinear),
+            "area" | "box" => Ok(ResampleMode::Area),
+            _ => Err(format!("unknown resample mode '{}', expected nearest, bilinear or area", s)),
+        }
+    }
+}
+
+// Clockwise rotation that turns a camera frame upright
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Rotate180,
+    Clockwise270
+}
+
+impl Rotation {
+    pub fn degrees(&self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 90,
+            Rotation::Rotate180 => 180,
+            Rotation::Clockwise270 => 270,
+        }
+    }
+
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::None),
+            90 => Some(Rotation::Clockwise90),
+            180 => Some(Rotation::Rotate180),
+            270 => Some(Rotation::Clockwise270),
+            _ => None,
+        }
+    }
+
+    // A quarter turn swaps width and height
+    pub fn swaps_axes(&self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Clockwise270)
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u32>()
+            .ok()
+            .and_then(Rotation::from_degrees)
+            .ok_or_else(|| format!("unknown rotation '{}', expected 0, 90, 180 or 270", s))
+    }
+}
+
+// Axes an image is mirrored across, horizontal mirroring is the usual selfie view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mirror {
+    None,
+    Horizontal,
+    Vertical,
+    Both
+}
+
+impl Mirror {
+    pub fn horizontal(&self) -> bool {
+        matches!(self, Mirror::Horizontal | Mirror::Both)
+    }
+
+    pub fn vertical(&self) -> bool {
+        matches!(self, Mirror::Vertical | Mirror::Both)
+    }
+}
+
+impl FromStr for Mirror {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Mirror::None),
+            "horizontal" | "selfie" => Ok(Mirror::Horizontal),
+            "vertical" => Ok(Mirror::Vertical),
+            "both" => Ok(Mirror::Both),
+            _ => Err(format!("unknown mirror mode '{}', expected none, horizontal, vertical or both", s)),
+        }
+    }
+}
+
+// One plane of an uncompressed image: byte offset, stride, rows, used bytes per row
+// and the size of the unit that must stay intact when mirroring columns
+struct Plane {
+    offset: usize,
+    stride: usize,
+    rows: usize,
+    row_bytes: usize,
+    unit: usize,
+}
+
+// What turns camera frames into BGR Mats for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayConverter {
+    // our fixed-point kernels, full range like the model input
+    Native,
+    // cvtColor, which treats YUV as BT.601 limited range
+    OpenCv
+}
+
+impl FromStr for DisplayConverter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "native" => Ok(DisplayConverter::Native),
+            "opencv" => Ok(DisplayConverter::OpenCv),
+            _ => Err(format!("unknown display converter '{}', expected native or opencv", s)),
+        }
+    }
+}
+
+// Where the client and the local mode get their frames, see source::FrameSource
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceSpec {
+    V4l2,
+    OpenCv,
+    // an image, or a directory of them
+    File(String),
+    // a recording made with --record
+    Replay(String),
+    Synthetic
+}
+
+impl FromStr for SourceSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(SourceSpec::File(path.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("replay:") {
+            return Ok(SourceSpec::Replay(path.to_string()));
+        }
+        match s.to_lowercase().as_str() {
+            "v4l2" => Ok(SourceSpec::V4l2),
+            "opencv" => Ok(SourceSpec::OpenCv),
+            "synthetic" => Ok(SourceSpec::Synthetic),
+            _ => Err(format!("unknown source '{}', expected v4l2, opencv, synthetic, file:<path> or replay:<path>", s)),
+        }
+    }
+}
+
+// How fast a recording plays back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayTiming {
+    // frames are as far apart as when they were recorded
+    Original,
+    // each frame as soon as it is asked for
+    Fast
+}
+
+impl FromStr for ReplayTiming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "original" => Ok(ReplayTiming::Original),
+            "fast" => Ok(ReplayTiming::Fast),
+            _ => Err(format!("unknown replay timing '{}', expected original or fast", s)),
+        }
+    }
+}
+
+// Everything about a frame but its bytes, as it travels between client and server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInfo {
+    pub width: u32,
+    pub height: u32,
+    pub stride: usize,
+    pub pixel_format: PixelFormat,
+    pub colorimetry: Colorimetry,
+    pub rotation: Rotation, // what turns the frame upright
+    pub timestamp: u64,
+    pub sequence: u32,
+}
+
 pub struct Image {
-    pub(crate) timestamp: u64,
+    pub(crate) timestamp: u64, // capture time, see monotonic_micros
+    pub(crate) sequence: u32, // frame counter of the source, 0 when not from one
     pub(crate) data: Vec<u8>,
     pub(crate) width: i32,
     pub(crate) height: i32,
-    pub(crate) color_space: COLOR_SPACE // either RGB or YUV
+    pub(crate) stride: usize, // bytes from one row to the next, may include padding
+    pub(crate) pixel_format: PixelFormat,
+    pub(crate) colorimetry: Colorimetry // only meaningful for YUV formats
 }
 
 pub struct ImageBuffer<'a> {
@@ -29,99 +343,198 @@ pub struct ImageBuffer<'a> {
     pub(crate) buffer: &'a [u8],
     pub(crate) width: i32,
     pub(crate) height: i32,
-    pub(crate) color_space: COLOR_SPACE, // either RGB or YUV
+    pub(crate) stride: usize,
+    pub(crate) pixel_format: PixelFormat,
+    pub(crate) colorimetry: Colorimetry,
     pub(crate) length: i32
 }
 
 impl Image {
 
-    pub fn new(data: Vec<u8>, width: i32, height: i32, color_space: COLOR_SPACE) -> Image {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    pub fn new(data: Vec<u8>, width: i32, height: i32, pixel_format: PixelFormat) -> Image {
+        let stride = pixel_format.packed_stride(width as usize);
+        Image::with_stride(data, width, height, stride, pixel_format)
+    }
+
+    // An image whose rows are `stride` bytes apart, e.g. V4L2 bytesperline
+    pub fn with_stride(data: Vec<u8>, width: i32, height: i32, stride: usize, pixel_format: PixelFormat) -> Image {
+        let timestamp = monotonic_micros();
+
+        debug_assert!(stride >= pixel_format.packed_stride(width as usize), "Stride is shorter than a row");
 
         Image {
             timestamp,
+            sequence: 0,
             data,
             width,
             height,
-            color_space
+            stride,
+            pixel_format,
+            colorimetry: Colorimetry::default()
         }
     }
-    pub fn from_mat(image: &Mat) -> Image {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
 
-        let bytes = image.data_bytes().unwrap();
-        let mut data = Vec::with_capacity(bytes.len());
-        data.extend_from_slice(bytes);
-
-        Image {
-            timestamp,
-            data,
-            width: image.cols() as i32,
-            height: image.rows() as i32,
-            color_space: RGB
-        }
+    // Stamps the image with when and as which frame the camera captured it
+    pub fn with_capture_info(mut self, timestamp: u64, sequence: u32) -> Image {
+        self.timestamp = timestamp;
+        self.sequence = sequence;
+        self
     }
 
-    // pub fn from_image_buffer(image_buffer: ImageBuffer) -> Self {
-    //     // copy the data from the buffer. Note the buffer is in yuv
-    //     let mut data = Vec::with_capacity((image_buffer.length * 3) as usize);
-    //     // copy the data from the buffer
-    //
-    // }
+    // Tags YUV data with the matrix and range the camera encoded it with
+    pub fn with_colorimetry(mut self, colorimetry: Colorimetry) -> Image {
+        self.colorimetry = colorimetry;
+        self
+    }
 
-    pub fn flip(&mut self) {
-        let width = self.width as usize;
-        let height = self.height as usize;
-        let bytes_per_pixel = 3;
+    // Describes this image for sending, `rotation` turns it upright
+    pub fn frame_info(&self, rotation: Rotation) -> FrameInfo {
+        FrameInfo {
+            width: self.width as u32,
+            height: self.height as u32,
+            stride: self.stride,
+            pixel_format: self.pixel_format,
+            colorimetry: self.colorimetry,
+            rotation,
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+        }
+    }
 
-        for y in 0..height {
-            let start_index = y * width * bytes_per_pixel;
-            let end_index = start_index + width * bytes_per_pixel;
+    // Whether rows follow each other without padding
+    pub fn is_packed(&self) -> bool {
+        self.stride == self.pixel_format.packed_stride(self.width as usize)
+    }
 
-            let row = &mut self.data[start_index..end_index];
+    // Copies a borrowed buffer into an owned Image in one go, keeping its stride
+    pub fn from_image_buffer(image_buffer: &ImageBuffer) -> Self {
+        Image {
+            timestamp: image_buffer.timestamp,
+            sequence: 0,
+            data: image_buffer.buffer.to_vec(),
+            width: image_buffer.width,
+            height: image_buffer.height,
+            stride: image_buffer.stride,
+            pixel_format: image_buffer.pixel_format,
+            colorimetry: image_buffer.colorimetry
+        }
+    }
 
-            row.chunks_exact_mut(bytes_per_pixel).for_each(|chunk| chunk.reverse());
+    fn planes(&self) -> Vec<Plane> {
+        let (width, height, stride) = (self.width as usize, self.height as usize, self.stride);
+        let plane = |offset, stride, rows, row_bytes, unit| Plane { offset, stride, rows, row_bytes, unit };
+        match self.pixel_format {
+            PixelFormat::RGB | PixelFormat::BGR => vec![plane(0, stride, height, width * 3, 3)],
+            PixelFormat::GREY => vec![plane(0, stride, height, width, 1)],
+            PixelFormat::RGB565 => vec![plane(0, stride, height, width * 2, 2)],
+            // whole macropixels, the two lumas inside are swapped afterwards
+            PixelFormat::YUYV | PixelFormat::UYVY => vec![plane(0, stride, height, width * 2, 4)],
+            PixelFormat::NV12 => vec![
+                plane(0, stride, height, width, 1),
+                plane(stride * height, stride, height / 2, width, 2),
+            ],
+            PixelFormat::YU12 => {
+                let chroma_stride = stride / 2;
+                let u_plane = stride * height;
+                vec![
+                    plane(0, stride, height, width, 1),
+                    plane(u_plane, chroma_stride, height / 2, width / 2, 1),
+                    plane(u_plane + chroma_stride * (height / 2), chroma_stride, height / 2, width / 2, 1),
+                ]
+            }
+            PixelFormat::MJPEG => unreachable!("compressed frames have no planes"),
         }
     }
 
-    pub fn to_mat(&self) -> Mat {
+    /** Mirrors the image in place, keeping pixels (and 4:2:2 macropixels or
+    ** NV12 UV pairs) intact, so it works for every pixel format. Compressed
+    ** frames are decoded to BGR first, which fails on a damaged frame.
+    **/
+    pub fn mirror(&mut self, mirror: Mirror) -> Result<(), String> {
+        if mirror == Mirror::None {
+            return Ok(());
+        }
+        if self.pixel_format.is_compressed() {
+            *self = self.to_bgr()?;
+        }
+        if matches!(self.pixel_format, PixelFormat::YUYV | PixelFormat::UYVY | PixelFormat::NV12 | PixelFormat::YU12) {
+            assert_eq!(self.width % 2, 0, "{:?} width must be even to mirror", self.pixel_format);
+        }
 
+        for plane in self.planes() {
+            if mirror.horizontal() {
+                for y in 0..plane.rows {
+                    let start = plane.offset + y * plane.stride;
+                    let row = &mut self.data[start..start + plane.row_bytes];
+                    // reversing the row and then every unit moves units without scrambling them
+                    row.reverse();
+                    row.chunks_exact_mut(plane.unit).for_each(|unit| unit.reverse());
+                }
+            }
+            if mirror.vertical() {
+                for y in 0..plane.rows / 2 {
+                    let top = plane.offset + y * plane.stride;
+                    let bottom = plane.offset + (plane.rows - 1 - y) * plane.stride;
+                    let (head, tail) = self.data.split_at_mut(bottom);
+                    head[top..top + plane.row_bytes].swap_with_slice(&mut tail[..plane.row_bytes]);
+                }
+            }
+        }
 
-        let thisimage:&Image = match &self.color_space {
-            COLOR_SPACE::YUV => {
-                let mut img2_rgb = vec![0; self.width as usize * self.height as usize * 3];
-                yuv422_to_rgb24(&self.data, &mut img2_rgb);
-                &Image {
-                    timestamp: 0,
-                    data: img2_rgb,
-                    width: self.width,
-                    height: self.height,
-                    color_space: RGB
+        // a mirrored macropixel shows its right pixel on the left
+        if mirror.horizontal() {
+            let luma = match self.pixel_format {
+                PixelFormat::YUYV => Some((0, 2)),
+                PixelFormat::UYVY => Some((1, 3)),
+                _ => None,
+            };
+            if let Some((first, second)) = luma {
+                for y in 0..self.height as usize {
+                    let start = y * self.stride;
+                    self.data[start..start + self.width as usize * 2]
+                        .chunks_exact_mut(4)
+                        .for_each(|macropixel| macropixel.swap(first, second));
                 }
-            },
-            COLOR_SPACE::RGB => self
-        };
-        let rows = thisimage.height as usize;
-        let cols = thisimage.width as usize;
-
-        let mut vec_2d_rgb: Vec<Vec<Vec3b>> = vec![vec![Vec3b::default(); cols]; rows];
-        for i in 0..rows {
-            for j in 0..cols {
-                let index = (i * cols + j) * 3;
-                vec_2d_rgb[i][j] = Vec3b::from_array([
-                    thisimage.data[index],     // B
-                    thisimage.data[index + 1],     // G
-                    thisimage.data[index + 2]          // R
-                ]);
             }
         }
-        Mat::from_slice_2d(&vec_2d_rgb).unwrap()
+        Ok(())
+    }
+
+    // Converts any pixel format to BGR for display, decoding compressed frames
+    // first; webcams do send the odd truncated frame, which fails here
+    pub fn to_bgr(&self) -> Result<Image, String> {
+        if self.pixel_format.is_compressed() {
+            let decoded = jpeg::decode(&self.data, None)?;
+            return Ok(decoded.to_bgr()?.with_capture_info(self.timestamp, self.sequence));
+        }
+
+        let mut data = vec![0; self.width as usize * self.height as usize * 3];
+        convert_to_rgb24(self, &mut data);
+        Ok(Image {
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+            data,
+            width: self.width,
+            height: self.height,
+            stride: self.width as usize * 3,
+            pixel_format: PixelFormat::BGR,
+            colorimetry: Colorimetry::default()
+        })
+    }
+
+    // Converts any pixel format to packed RGB, the channel order image files use
+    pub fn to_rgb(&self) -> Result<Image, String> {
+        if self.pixel_format == PixelFormat::RGB && self.is_packed() {
+            return Ok(Image { data: self.data.clone(), ..*self });
+        }
+        let mut rgb = if self.pixel_format == PixelFormat::BGR && self.is_packed() {
+            Image { data: self.data.clone(), ..*self }
+        } else {
+            self.to_bgr()?
+        };
+        rgb.data.chunks_exact_mut(3).for_each(|pixel| pixel.swap(0, 2));
+        rgb.pixel_format = PixelFormat::RGB;
+        Ok(rgb)
     }
 }
 
@@ -137,9 +550,93 @@ pub struct Arguments {
     #[structopt(short="m", long="main", help = "Run Main")]
     pub main: bool,
 
+    #[structopt(long="bench-convert", help = "Benchmark and verify the YUV conversion kernels")]
+    pub bench_convert: bool,
+
+    #[structopt(long="bench-preprocess", help = "Benchmark preprocess_into against separate resize, conversion and copy steps; needs the bench feature")]
+    pub bench_preprocess: bool,
+
+    #[structopt(long="annotate", help = "Run the model on a JPEG file and write it with the pose drawn, see --output")]
+    pub annotate: Option<String>,
+
+    #[structopt(long="output", default_value = "annotated.png", help = "Output of --annotate, .png, .ppm or .jpg")]
+    pub output: String,
+
     #[structopt(short="bind", long="bind", default_value = "127.0.0.1:10026", help = "Bind address, only use for server")]
     pub bind: String,
 
     #[structopt(short="a", long="connect", default_value = "127.0.0.1:10026", help = "Connect address, only use for client")]
     pub connect: String,
+
+    #[structopt(long="resample", default_value = "nearest", help = "Resampling used when downscaling frames: nearest, bilinear or area")]
+    pub resample: ResampleMode,
+
+    #[structopt(long="pixel-format", help = "Camera pixel format: yuyv, uyvy, nv12, yu12, grey, rgb565 or mjpeg, picks the best one the camera offers when not given, only use for client")]
+    pub pixel_format: Option<PixelFormat>,
+
+    #[structopt(long="width", default_value = "640", help = "Requested camera width, the closest size the camera offers is used, only use for client")]
+    pub width: u32,
+
+    #[structopt(long="height", default_value = "480", help = "Requested camera height, only use for client")]
+    pub height: u32,
+
+    #[structopt(long="fps", default_value = "30", help = "Requested camera frame rate, only use for client")]
+    pub fps: u32,
+
+    #[structopt(long="controls", help = "File of camera control presets, one name = value per line, applied at startup, only use for client")]
+    pub controls: Option<String>,
+
+    #[structopt(long="list-controls", help = "Print the camera controls with their ranges and values and exit, only use for client")]
+    pub list_controls: bool,
+
+    #[structopt(long="get-control", help = "Print the value of one camera control and exit, only use for client")]
+    pub get_control: Option<String>,
+
+    #[structopt(long="set-control", help = "Set a camera control, name=value, and exit; may be repeated, only use for client")]
+    pub set_control: Vec<String>,
+
+    #[structopt(long="source", help = "Frame source: v4l2, opencv, synthetic, file:<image or directory> or replay:<recording>, defaults to v4l2 for the client and opencv for -m. Sources, replay included, feed the client or -m, so they need the opencv feature")]
+    pub source: Option<SourceSpec>,
+
+    #[structopt(long="camera", default_value = "0", help = "Camera to open: index, /dev path, /dev/v4l/by-id link, bus_info or card name; for the opencv source an index or video file")]
+    pub camera: String,
+
+    #[structopt(long="repeat", help = "Start a file or replay source over after its last frame")]
+    pub repeat: bool,
+
+    #[structopt(long="record", help = "Write every frame from the source, raw and with its timestamp, to this recording, only use for client")]
+    pub record: Option<String>,
+
+    #[structopt(long="replay-timing", default_value = "original", help = "Pace of replay sources: original or fast")]
+    pub replay_timing: ReplayTiming,
+
+    #[structopt(long="list-cameras", help = "Print the capture cameras found with their names and exit")]
+    pub list_cameras: bool,
+
+    #[structopt(long="buffers", default_value = "4", help = "Number of V4L2 capture buffers to request, only use for client")]
+    pub buffers: u32,
+
+    #[structopt(long="capture-timeout", default_value = "1000", help = "Milliseconds to wait for a camera frame before reporting a stall, only use for client")]
+    pub capture_timeout: u64,
+
+    #[structopt(long="reconnect-interval", default_value = "1000", help = "Milliseconds between attempts to reopen an unplugged camera, only use for client")]
+    pub reconnect_interval: u64,
+
+    #[structopt(long="rotation", default_value = "0", help = "Clockwise degrees (0, 90, 180 or 270) that turn the camera image upright, only use for client")]
+    pub rotation: Rotation,
+
+    #[structopt(long="mirror", default_value = "none", help = "Mirror the displayed image and keypoints: none, horizontal (selfie), vertical or both, only use for client")]
+    pub mirror: Mirror,
+
+    #[structopt(long="swap-sides", help = "Swap left and right joint labels of the displayed keypoints, only use for client")]
+    pub swap_sides: bool,
+
+    #[structopt(long="colorimetry", help = "Override the YUV colorimetry the camera reports: bt601-full, bt601-limited, bt709-full or bt709-limited, only use for client")]
+    pub colorimetry: Option<Colorimetry>,
+
+    #[structopt(long="display-converter", default_value = "native", help = "Converter used for the displayed frames: native or opencv, only use for client")]
+    pub display_converter: DisplayConverter,
+
+    #[structopt(long="model", help = "Path to a MoveNet .tflite model, defaults to the embedded lightning model")]
+    pub model: Option<String>,
 }
\ No newline at end of file
@@ -1,11 +1,18 @@
+// OpenCV helpers used by the original demo, the rest of this file is plain Rust
+#[cfg(feature = "opencv")]
 use opencv::{
 	prelude::*,
 	imgproc::*,
 	core::*,
 };
+use std::cell::RefCell;
+use std::sync::Arc;
 use rayon::prelude::*;
-use crate::types::{Image, COLOR_SPACE};
+use crate::types::{Image, PixelFormat, ResampleMode, Rotation};
+use crate::convert::{self, YuvCoefficients};
+use crate::pool::BufferPool;
 
+#[cfg(feature = "opencv")]
 pub fn resize_with_padding(img: &Mat, new_shape: [i32;2]) -> Mat {
 	let img_shape = [img.cols(), img.rows()];
 	let width: i32;
@@ -43,6 +50,7 @@ pub fn resize_with_padding(img: &Mat, new_shape: [i32;2]) -> Mat {
 	rslt
 }
 
+#[cfg(feature = "opencv")]
 pub fn draw_keypoints(img: &mut Mat, keypoints: &[f32], threshold: f32) {
 	// keypoints: [1, 17, 3]
 	let base: f32;
@@ -171,15 +179,28 @@ pub fn draw_keypoints(img: &mut Mat, keypoints: &[f32], threshold: f32) {
 //
 // 	dst
 // }
+/** Resizes with padding while keeping the pixel format. RGB, BGR, GREY and the
+** packed 4:2:2 formats are resized in place; the remaining formats are converted
+** to RGB on the way through preprocess_into. Compressed frames must be decoded
+** first. The result and the intermediate resize buffer come from `pool`.
+**/
 pub fn resize_with_padding_ultra_fast(
 	img: &Image,
 	(new_width, new_height): (i32, i32),
-	color_type: COLOR_SPACE
+	mode: ResampleMode,
+	pool: &BufferPool
 ) -> Image {
 	let (original_width, original_height) = (img.width, img.height);
-	let channels = match color_type {
-		COLOR_SPACE::RGB => 3,
-		COLOR_SPACE::YUV => 2,
+	assert!(!img.pixel_format.is_compressed(), "{:?} frames must be decoded before resizing", img.pixel_format);
+	let channels = match img.pixel_format {
+		PixelFormat::RGB | PixelFormat::BGR => 3,
+		PixelFormat::YUYV | PixelFormat::UYVY => 2,
+		PixelFormat::GREY => 1,
+		PixelFormat::NV12 | PixelFormat::YU12 | PixelFormat::RGB565 | PixelFormat::MJPEG => {
+			let mut rgb = pool.take_image(new_width, new_height, PixelFormat::RGB);
+			preprocess_into(img, &mut rgb.data, (new_width, new_height), mode);
+			return rgb.with_capture_info(img.timestamp, img.sequence);
+		}
 	};
 
 	// Calculate scaling to maintain aspect ratio
@@ -189,19 +210,33 @@ pub fn resize_with_padding_ultra_fast(
 		new_height as f32 / original_height as f32
 	};
 
-	let scaled_width = (original_width as f32 * scale) as i32;
+	let mut scaled_width = (original_width as f32 * scale) as i32;
 	let scaled_height = (original_height as f32 * scale) as i32;
 
-	// First resize the image
-	let resized = resize_fast_downsample(img, (scaled_width, scaled_height), channels);
-
 	// Calculate padding
-	let delta_w = new_width - scaled_width;
 	let delta_h = new_height - scaled_height;
-	let pad_left = delta_w / 2;
 	let pad_top = delta_h / 2;
+	let pad_left;
+
+	// First resize the image
+	let resized = if channels == 2 {
+		// keep whole macropixels: even width and an even left offset
+		scaled_width &= !1;
+		pad_left = ((new_width - scaled_width) / 2) & !1;
+		resize_packed_422(img, (scaled_width, scaled_height), mode, pool)
+	} else {
+		pad_left = (new_width - scaled_width) / 2;
+		resize_channels(img, (scaled_width, scaled_height), channels, mode, pool)
+	};
 
-	let mut final_image = vec![0u8; (new_width * new_height * channels as i32) as usize];
+	// black padding, which is not all zeros in 4:2:2 (and luma 16 in limited range)
+	let black = img.colorimetry.coefficients().y_offset as u8;
+	let mut final_image = pool.take((new_width * new_height * channels as i32) as usize);
+	match img.pixel_format {
+		PixelFormat::YUYV => final_image.chunks_exact_mut(2).for_each(|pixel| pixel.copy_from_slice(&[black, 128])),
+		PixelFormat::UYVY => final_image.chunks_exact_mut(2).for_each(|pixel| pixel.copy_from_slice(&[128, black])),
+		_ => final_image.fill(0),
+	}
 
 	// Copy resized image into padded final image
 	final_image.par_chunks_exact_mut(new_width as usize * channels)
@@ -217,44 +252,137 @@ pub fn resize_with_padding_ultra_fast(
 				);
 			}
 		});
+	pool.give(resized);
 
 	Image {
 		timestamp: img.timestamp,
+		sequence: img.sequence,
 		width: new_width,
 		height: new_height,
+		stride: new_width as usize * channels,
 		data: final_image,
-		color_space: color_type,
+		pixel_format: img.pixel_format,
+		colorimetry: img.colorimetry,
 	}
 }
 
 
 
 
-fn resize_fast_downsample(
+// Source pixels (first index and weights) contributing to one destination coordinate
+struct AxisTaps {
+	first: usize,
+	weights: Vec<f32>,
+}
+
+/** Computes, for every destination coordinate along one axis, which source
+** coordinates it samples and with what weight. The step between destination
+** pixels is the exact fractional ratio src_len / dst_len.
+**/
+fn axis_taps(src_len: usize, dst_len: usize, mode: ResampleMode) -> Vec<AxisTaps> {
+	// nothing to sample from
+	if src_len == 0 {
+		return Vec::new();
+	}
+	let ratio = src_len as f32 / dst_len as f32;
+	let last = src_len - 1;
+
+	(0..dst_len).map(|d| {
+		match mode {
+			ResampleMode::Nearest => {
+				let center = (d as f32 + 0.5) * ratio;
+				AxisTaps { first: (center as usize).min(last), weights: vec![1.0] }
+			}
+			ResampleMode::Bilinear => {
+				let center = ((d as f32 + 0.5) * ratio - 0.5).max(0.0);
+				let first = (center as usize).min(last);
+				let frac = center - first as f32;
+				if first == last || frac == 0.0 {
+					AxisTaps { first, weights: vec![1.0] }
+				} else {
+					AxisTaps { first, weights: vec![1.0 - frac, frac] }
+				}
+			}
+			ResampleMode::Area => {
+				// average every source pixel overlapped by [start, end), weighted by coverage
+				let start = d as f32 * ratio;
+				let end = ((d + 1) as f32 * ratio).min(src_len as f32);
+				let first = (start as usize).min(last);
+				let weights = (first..(end.ceil() as usize).max(first + 1).min(src_len))
+					.map(|i| ((i + 1) as f32).min(end) - (i as f32).max(start))
+					.map(|coverage| coverage / (end - start))
+					.collect();
+				AxisTaps { first, weights }
+			}
+		}
+	}).collect()
+}
+
+// Tap tables in use on this thread, the most recent last. The server serves each
+// connection on a thread of its own, so a stream of same-sized frames builds its
+// tables once instead of allocating every tap again for every frame.
+type TapKey = (usize, usize, ResampleMode);
+thread_local! {
+	static TAP_CACHE: RefCell<Vec<(TapKey, Arc<[AxisTaps]>)>> = const { RefCell::new(Vec::new()) };
+}
+// enough for the three axes resize_packed_422 uses at two frame sizes
+const TAP_CACHE_SIZE: usize = 6;
+
+fn cached_axis_taps(src_len: usize, dst_len: usize, mode: ResampleMode) -> Arc<[AxisTaps]> {
+	TAP_CACHE.with(|cache| {
+		let mut cache = cache.borrow_mut();
+		let key = (src_len, dst_len, mode);
+		if let Some((_, taps)) = cache.iter().find(|(cached, _)| *cached == key) {
+			return Arc::clone(taps);
+		}
+		let taps: Arc<[AxisTaps]> = axis_taps(src_len, dst_len, mode).into();
+		if cache.len() == TAP_CACHE_SIZE {
+			cache.remove(0);
+		}
+		cache.push((key, Arc::clone(&taps)));
+		taps
+	})
+}
+
+fn resize_channels(
 	src_image: &Image,
 	(dst_width, dst_height): (i32, i32),
 	channels: usize,
+	mode: ResampleMode,
+	pool: &BufferPool,
 ) -> Vec<u8> {
 	let src = &src_image.data;
-	let (src_width, src_height) = (src_image.width, src_image.height);
-	let mut dst = vec![0u8; (dst_width * dst_height * channels as i32) as usize];
+	let (src_width, src_height) = (src_image.width as usize, src_image.height as usize);
+	let src_stride = src_image.stride;
+	// every byte is written below, so stale pooled contents do not matter
+	let mut dst = pool.take((dst_width * dst_height * channels as i32) as usize);
 
-	// Calculate step sizes
-	let x_step = (src_width as f32 / dst_width as f32).max(1.0) as i32;
-	let y_step = (src_height as f32 / dst_height as f32).max(1.0) as i32;
+	let x_taps = cached_axis_taps(src_width, dst_width as usize, mode);
+	let y_taps = cached_axis_taps(src_height, dst_height as usize, mode);
 
 	dst.par_chunks_exact_mut(dst_width as usize * channels)
 		.enumerate()
 		.for_each(|(y, row)| {
-			let src_y = (y as i32 * y_step) as usize;
-			for x in 0..dst_width as usize {
-				let src_x = (x as i32 * x_step) as usize;
-				let src_idx = (src_y * src_width as usize + src_x) * channels;
+			let y_tap = &y_taps[y];
+			for (x, x_tap) in x_taps.iter().enumerate() {
 				let dst_idx = x * channels;
 
-				// Direct copy of pixel values
+				if let ResampleMode::Nearest = mode {
+					// Direct copy of pixel values
+					let src_idx = y_tap.first * src_stride + x_tap.first * channels;
+					row[dst_idx..dst_idx + channels].copy_from_slice(&src[src_idx..src_idx + channels]);
+					continue;
+				}
+
 				for c in 0..channels {
-					row[dst_idx + c] = src[src_idx + c];
+					let mut sum = 0.0;
+					for (ty, wy) in y_tap.weights.iter().enumerate() {
+						let src_row = (y_tap.first + ty) * src_stride;
+						for (tx, wx) in x_tap.weights.iter().enumerate() {
+							sum += wy * wx * src[src_row + (x_tap.first + tx) * channels + c] as f32;
+						}
+					}
+					row[dst_idx + c] = clamp(sum);
 				}
 			}
 		});
@@ -262,46 +390,347 @@ fn resize_fast_downsample(
 	dst
 }
 
-// https://stackoverflow.com/questions/28079010/rgb-to-ycbcr-using-simd-vectors-lose-some-data
-pub fn yuv422_to_rgb24(in_buf: &[u8], out_buf: &mut [u8]) {
+/** Resizes a YUYV or UYVY image without breaking up its macropixels. Luma is
+** resampled per pixel, while U and V are resampled on the half-width chroma
+** grid, so every output macropixel keeps the chroma of the source area it
+** covers. dst_width must be even.
+**/
+fn resize_packed_422(
+	src_image: &Image,
+	(dst_width, dst_height): (i32, i32),
+	mode: ResampleMode,
+	pool: &BufferPool,
+) -> Vec<u8> {
+	debug_assert_eq!(dst_width % 2, 0, "4:2:2 width must be even");
+
+	// byte offsets of Y0, U, Y1 and V inside a macropixel
+	let layout = match src_image.pixel_format {
+		PixelFormat::UYVY => [1, 0, 3, 2],
+		_ => [0, 1, 2, 3],
+	};
+
+	let src = &src_image.data;
+	let (src_width, src_height) = (src_image.width as usize, src_image.height as usize);
+	let src_stride = src_image.stride;
+	let mut dst = pool.take((dst_width * dst_height * 2) as usize);
+
+	let luma_taps = cached_axis_taps(src_width, dst_width as usize, mode);
+	let chroma_taps = cached_axis_taps(src_width / 2, dst_width as usize / 2, mode);
+	let y_taps = cached_axis_taps(src_height, dst_height as usize, mode);
+
+	// weighted sum over one row tap set and one column tap set, `step` bytes
+	// apart along the row, byte `offset` selects the component
+	let sample = |y_tap: &AxisTaps, x_tap: &AxisTaps, step: usize, offset: usize| -> u8 {
+		let mut sum = 0.0;
+		for (ty, wy) in y_tap.weights.iter().enumerate() {
+			let src_row = (y_tap.first + ty) * src_stride;
+			for (tx, wx) in x_tap.weights.iter().enumerate() {
+				sum += wy * wx * src[src_row + (x_tap.first + tx) * step + offset] as f32;
+			}
+		}
+		clamp(sum)
+	};
+
+	dst.par_chunks_exact_mut(dst_width as usize * 2)
+		.enumerate()
+		.for_each(|(y, row)| {
+			let y_tap = &y_taps[y];
+			for (k, out) in row.chunks_exact_mut(4).enumerate() {
+				out[layout[0]] = sample(y_tap, &luma_taps[2 * k], 2, layout[0] & 1);
+				out[layout[1]] = sample(y_tap, &chroma_taps[k], 4, layout[1]);
+				out[layout[2]] = sample(y_tap, &luma_taps[2 * k + 1], 2, layout[0] & 1);
+				out[layout[3]] = sample(y_tap, &chroma_taps[k], 4, layout[3]);
+			}
+		});
+
+	dst
+}
+
+/** Rotates an image clockwise by a multiple of 90 degrees. RGB, BGR and GREY are
+** remapped per pixel. YUYV and UYVY are rebuilt one output macropixel at a time:
+** a quarter turn pairs up pixels from two source macropixels, so their chroma is
+** averaged. An odd height turned a quarter has no whole macropixels, so those
+** frames are converted to BGR first, like the other formats. The result is
+** packed and its buffer comes from `pool`. Compressed frames must be decoded first,
+** and subsampled ones must hold whole chroma samples, which handle_client checks.
+**/
+pub fn rotate_image(img: &Image, rotation: Rotation, pool: &BufferPool) -> Image {
+	assert!(!img.pixel_format.is_compressed(), "{:?} frames must be decoded before rotating", img.pixel_format);
+	let (chroma_width, chroma_height) = img.pixel_format.subsampling();
+	assert!(
+		(img.width as usize).is_multiple_of(chroma_width) && (img.height as usize).is_multiple_of(chroma_height),
+		"{:?} frames cannot be {}x{}", img.pixel_format, img.width, img.height
+	);
+	let channels = match img.pixel_format {
+		PixelFormat::RGB | PixelFormat::BGR => 3,
+		PixelFormat::YUYV | PixelFormat::UYVY if !rotation.swaps_axes() || img.height % 2 == 0 => 2,
+		PixelFormat::GREY => 1,
+		_ => {
+			let mut bgr = pool.take_image(img.width, img.height, PixelFormat::BGR);
+			convert_to_rgb24(img, &mut bgr.data);
+			let rotated = rotate_image(&bgr, rotation, pool);
+			pool.recycle(bgr);
+			return rotated;
+		}
+	};
+
+	let (width, height) = (img.width as usize, img.height as usize);
+	let (dst_width, dst_height) = if rotation.swaps_axes() { (height, width) } else { (width, height) };
+	let stride = img.stride;
+
+	// source pixel that lands on (x, y) of the rotated image
+	let source_of = |x: usize, y: usize| -> (usize, usize) {
+		match rotation {
+			Rotation::None => (x, y),
+			Rotation::Clockwise90 => (y, height - 1 - x),
+			Rotation::Rotate180 => (width - 1 - x, height - 1 - y),
+			Rotation::Clockwise270 => (width - 1 - y, x),
+		}
+	};
+
+	let mut data = pool.take(dst_width * dst_height * channels);
+	if channels == 2 {
+		assert_eq!(dst_width % 2, 0, "rotated 4:2:2 width must be even");
+
+		// byte offsets of Y0, U, Y1 and V inside a macropixel
+		let layout = match img.pixel_format {
+			PixelFormat::UYVY => [1, 0, 3, 2],
+			_ => [0, 1, 2, 3],
+		};
+		// (luma, u, v) of one source pixel
+		let fetch = |(x, y): (usize, usize)| -> (u8, u8, u8) {
+			let macropixel = y * stride + (x & !1) * 2;
+			let luma = img.data[macropixel + layout[(x & 1) * 2]];
+			(luma, img.data[macropixel + layout[1]], img.data[macropixel + layout[3]])
+		};
+
+		data.par_chunks_exact_mut(dst_width * 2)
+			.enumerate()
+			.for_each(|(y, row)| {
+				for (k, out) in row.chunks_exact_mut(4).enumerate() {
+					let (y0, u0, v0) = fetch(source_of(2 * k, y));
+					let (y1, u1, v1) = fetch(source_of(2 * k + 1, y));
+					out[layout[0]] = y0;
+					out[layout[1]] = (u0 as u16 + u1 as u16).div_ceil(2) as u8;
+					out[layout[2]] = y1;
+					out[layout[3]] = (v0 as u16 + v1 as u16).div_ceil(2) as u8;
+				}
+			});
+	} else {
+		data.par_chunks_exact_mut(dst_width * channels)
+			.enumerate()
+			.for_each(|(y, row)| {
+				for (x, out) in row.chunks_exact_mut(channels).enumerate() {
+					let (src_x, src_y) = source_of(x, y);
+					let idx = src_y * stride + src_x * channels;
+					out.copy_from_slice(&img.data[idx..idx + channels]);
+				}
+			});
+	}
+
+	Image {
+		timestamp: img.timestamp,
+		sequence: img.sequence,
+		width: dst_width as i32,
+		height: dst_height as i32,
+		stride: dst_width * channels,
+		data,
+		pixel_format: img.pixel_format,
+		colorimetry: img.colorimetry,
+	}
+}
+
+/** Scales and pads an image of any pixel format straight into an RGB destination of
+** new_width x new_height pixels, e.g. the memory of the interpreter input tensor.
+** Resizing, padding and color conversion all happen in one parallel pass, so no
+** intermediate buffers are allocated. Compressed frames must be decoded first.
+**/
+pub fn preprocess_into(
+	img: &Image,
+	dst: &mut [u8],
+	(new_width, new_height): (i32, i32),
+	mode: ResampleMode
+) {
+	debug_assert_eq!(dst.len(), (new_width * new_height * 3) as usize, "Destination must hold new_width * new_height RGB pixels");
+	assert!(!img.pixel_format.is_compressed(), "{:?} frames must be decoded before preprocessing", img.pixel_format);
+
+	let (src_width, src_height) = (img.width, img.height);
+	// an empty frame is all padding, and would make the scale below infinite
+	if src_width == 0 || src_height == 0 {
+		dst.fill(0);
+		return;
+	}
+
+	// Calculate scaling to maintain aspect ratio
+	let scale = if (src_width * new_height) > (src_height * new_width) {
+		new_width as f32 / src_width as f32
+	} else {
+		new_height as f32 / src_height as f32
+	};
+	let scaled_width = (src_width as f32 * scale) as i32;
+	let scaled_height = (src_height as f32 * scale) as i32;
+	let pad_left = (new_width - scaled_width) / 2;
+	let pad_top = (new_height - scaled_height) / 2;
+
+	let x_taps = cached_axis_taps(src_width as usize, scaled_width as usize, mode);
+	let y_taps = cached_axis_taps(src_height as usize, scaled_height as usize, mode);
+	let coefficients = img.colorimetry.coefficients();
+
+	dst.par_chunks_exact_mut(new_width as usize * 3)
+		.enumerate()
+		.for_each(|(y, row)| {
+			let y = y as i32;
+			if y < pad_top || y >= pad_top + scaled_height {
+				row.fill(0);
+				return;
+			}
+			let y_tap = &y_taps[(y - pad_top) as usize];
+
+			for (x, out) in row.chunks_exact_mut(3).enumerate() {
+				let x = x as i32;
+				if x < pad_left || x >= pad_left + scaled_width {
+					out.fill(0);
+					continue;
+				}
+				let x_tap = &x_taps[(x - pad_left) as usize];
+
+				if let ResampleMode::Nearest = mode {
+					let (r, g, b) = fetch_rgb(img, coefficients, x_tap.first, y_tap.first);
+					out[0] = r;
+					out[1] = g;
+					out[2] = b;
+					continue;
+				}
+
+				let mut sum = [0.0f32; 3];
+				for (ty, wy) in y_tap.weights.iter().enumerate() {
+					for (tx, wx) in x_tap.weights.iter().enumerate() {
+						let (r, g, b) = fetch_rgb(img, coefficients, x_tap.first + tx, y_tap.first + ty);
+						let weight = wy * wx;
+						sum[0] += weight * r as f32;
+						sum[1] += weight * g as f32;
+						sum[2] += weight * b as f32;
+					}
+				}
+				out[0] = clamp(sum[0]);
+				out[1] = clamp(sum[1]);
+				out[2] = clamp(sum[2]);
+			}
+		});
+}
+
+// Reads one source pixel as RGB, converting from the pixel format as needed.
+// `coefficients` are those of img.colorimetry, looked up once by the caller
+#[inline]
+fn fetch_rgb(img: &Image, coefficients: &YuvCoefficients, x: usize, y: usize) -> (u8, u8, u8) {
+	let stride = img.stride;
+	let data = &img.data;
+	match img.pixel_format {
+		PixelFormat::RGB => {
+			let idx = y * stride + x * 3;
+			(data[idx], data[idx + 1], data[idx + 2])
+		}
+		PixelFormat::BGR => {
+			let idx = y * stride + x * 3;
+			(data[idx + 2], data[idx + 1], data[idx])
+		}
+		PixelFormat::YUYV => {
+			// a macropixel Y0 U Y1 V covers two pixels that share their chroma
+			let macropixel = y * stride + (x & !1) * 2;
+			let luma = data[macropixel + (x & 1) * 2];
+			ycbcr_to_rgb(coefficients, (luma, data[macropixel + 1], data[macropixel + 3]))
+		}
+		PixelFormat::UYVY => {
+			let macropixel = y * stride + (x & !1) * 2;
+			let luma = data[macropixel + 1 + (x & 1) * 2];
+			ycbcr_to_rgb(coefficients, (luma, data[macropixel], data[macropixel + 2]))
+		}
+		PixelFormat::NV12 => {
+			// one UV pair per 2x2 block, UV rows are as long as luma rows
+			let chroma = stride * img.height as usize + (y / 2) * stride + (x & !1);
+			ycbcr_to_rgb(coefficients, (data[y * stride + x], data[chroma], data[chroma + 1]))
+		}
+		PixelFormat::YU12 => {
+			let luma_size = stride * img.height as usize;
+			let chroma_stride = stride / 2;
+			let chroma = (y / 2) * chroma_stride + x / 2;
+			let v_plane = luma_size + chroma_stride * (img.height as usize / 2);
+			ycbcr_to_rgb(coefficients, (data[y * stride + x], data[luma_size + chroma], data[v_plane + chroma]))
+		}
+		PixelFormat::GREY => {
+			let luma = data[y * stride + x];
+			(luma, luma, luma)
+		}
+		PixelFormat::RGB565 => {
+			let idx = y * stride + x * 2;
+			let value = data[idx] as u16 | (data[idx + 1] as u16) << 8;
+			// expand to 8 bits by replicating the high bits into the low ones
+			let r = (value >> 11) as u8 & 0x1F;
+			let g = (value >> 5) as u8 & 0x3F;
+			let b = value as u8 & 0x1F;
+			((r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2))
+		}
+		PixelFormat::MJPEG => unreachable!("MJPEG frames are decoded before sampling"),
+	}
+}
+
+/** Converts a whole frame of any uncompressed pixel format to BGR pixels, the
+** channel order OpenCV displays and yuv422_to_rgb24 produces.
+**/
+pub fn convert_to_rgb24(img: &Image, out_buf: &mut [u8]) {
+	let width = img.width as usize;
+	debug_assert_eq!(out_buf.len(), width * img.height as usize * 3, "Output buffer must hold width * height RGB pixels");
+
+	let stride = img.stride;
+	let coefficients = img.colorimetry.coefficients();
+	match img.pixel_format {
+		PixelFormat::BGR if img.is_packed() => out_buf.copy_from_slice(&img.data[..out_buf.len()]),
+		PixelFormat::BGR => {
+			out_buf.par_chunks_exact_mut(width * 3)
+				.enumerate()
+				.for_each(|(y, row)| row.copy_from_slice(&img.data[y * stride..y * stride + width * 3]));
+		}
+		PixelFormat::YUYV if img.is_packed() => yuv422_to_rgb24(coefficients, &img.data[..out_buf.len() * 2 / 3], out_buf),
+		PixelFormat::YUYV => {
+			out_buf.par_chunks_exact_mut(width * 3)
+				.enumerate()
+				.for_each(|(y, row)| convert::yuyv_to_bgr(coefficients, &img.data[y * stride..y * stride + width * 2], row));
+		}
+		_ => {
+			out_buf.par_chunks_exact_mut(width * 3)
+				.enumerate()
+				.for_each(|(y, row)| {
+					for (x, out) in row.chunks_exact_mut(3).enumerate() {
+						let (r, g, b) = fetch_rgb(img, coefficients, x, y);
+						out[0] = b;
+						out[1] = g;
+						out[2] = r;
+					}
+				});
+		}
+	}
+}
+
+// Macropixels converted per rayon task, large enough to amortize the scheduling
+const CONVERT_CHUNK_PIXELS: usize = 4096;
+
+// YUYV -> BGR24 with the fixed-point kernels in `convert`, split across threads
+pub fn yuv422_to_rgb24(coefficients: &YuvCoefficients, in_buf: &[u8], out_buf: &mut [u8]) {
 	debug_assert_eq!(out_buf.len(), in_buf.len() * 3/2, "Output buffer length must be 3/2 of input buffer length");
 
 	in_buf
-		.par_chunks_exact(4)
-		.zip(out_buf.par_chunks_exact_mut(6))
-		.for_each(|(chunk, out)| {
-			let (y1, cb, y2, cr) = (chunk[0], chunk[1], chunk[2], chunk[3]);
-
-			let (r1, g1, b1) = ycbcr_to_rgb((y1, cb, cr));
-			let (r2, g2, b2) = ycbcr_to_rgb((y2, cb, cr));
-
-			out[0] = b1;
-			out[1] = g1;
-			out[2] = r1;
-			out[3] = b2;
-			out[4] = g2;
-			out[5] = r2;
-		});
+		.par_chunks(CONVERT_CHUNK_PIXELS * 2)
+		.zip(out_buf.par_chunks_mut(CONVERT_CHUNK_PIXELS * 3))
+		.for_each(|(chunk, out)| convert::yuyv_to_bgr(coefficients, chunk, out));
 }
 
-pub fn rgb24_to_yuv422(in_buf: &[u8], out_buf: &mut [u8]) {
+pub fn rgb24_to_yuv422(coefficients: &YuvCoefficients, in_buf: &[u8], out_buf: &mut [u8]) {
 	debug_assert_eq!(out_buf.len(), in_buf.len() * 2/3, "Output buffer length must be 2/3 of input buffer length");
 
 	in_buf
-		.par_chunks_exact(6)
-		.zip(out_buf.par_chunks_exact_mut(4))
-		.for_each(|(chunk, out)| {
-			let (r1, g1, b1) = (chunk[2], chunk[1], chunk[0]);
-			let (r2, g2, b2) = (chunk[5], chunk[4], chunk[3]);
-
-			let (y1, cb, cr) = rgb_to_ycbcr((r1, g1, b1));
-			let (y2, _, _) = rgb_to_ycbcr((r2, g2, b2));
-
-			out[0] = y1;
-			out[1] = cb;
-			out[2] = y2;
-			out[3] = cr;
-		});
+		.par_chunks(CONVERT_CHUNK_PIXELS * 3)
+		.zip(out_buf.par_chunks_mut(CONVERT_CHUNK_PIXELS * 2))
+		.for_each(|(chunk, out)| convert::bgr_to_yuyv(coefficients, chunk, out));
 }
 #[inline]
 fn clamp(val: f32) -> u8 {
@@ -309,26 +738,10 @@ fn clamp(val: f32) -> u8 {
 }
 
 #[inline]
-pub fn rgb_to_ycbcr((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
-	let r = r as f32;
-	let g = g as f32;
-	let b = b as f32;
-
-	let y = 0.299 * r + 0.587 * g + 0.114 * b;
-	let cb = -0.168736 * r - 0.331264 * g + 0.5 * b + 128.0;
-	let cr = 0.5 * r - 0.418688 * g - 0.081312 * b + 128.0;
-
-	(clamp(y), clamp(cb), clamp(cr))
+pub fn rgb_to_ycbcr(coefficients: &YuvCoefficients, rgb: (u8, u8, u8)) -> (u8, u8, u8) {
+	convert::scalar::rgb_to_yuv(coefficients, rgb)
 }
 #[inline]
-pub fn ycbcr_to_rgb((y, cb, cr): (u8, u8, u8)) -> (u8, u8, u8) {
-	let y = y as f32;
-	let cb = cb as f32 - 128.0;
-	let cr = cr as f32 - 128.0;
-
-	let r = y + 1.402 * cr;
-	let g = y - 0.344136 * cb - 0.714136 * cr;
-	let b = y + 1.772 * cb;
-
-	(clamp(r), clamp(g), clamp(b))
-}
\ No newline at end of file
+pub fn ycbcr_to_rgb(coefficients: &YuvCoefficients, ycbcr: (u8, u8, u8)) -> (u8, u8, u8) {
+	convert::scalar::yuv_to_rgb(coefficients, ycbcr)
+}